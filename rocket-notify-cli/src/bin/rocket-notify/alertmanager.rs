@@ -0,0 +1,448 @@
+use chrono::{DateTime, Datelike, Utc};
+use rocketchat::{
+    color::Color,
+    request::{Attachment, AttachmentField, Message},
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt::Write};
+
+/// Most alerts shown in a message by default
+pub const MAX_ALERTS: usize = 20;
+
+const FIRING: &str = "firing";
+
+// Annotations shown as the text of an alert, rather than as fields
+const TEXT_ANNOTATIONS: [&str; 2] = ["summary", "description"];
+
+/// An alert in a notification, rendered as an attachment
+pub trait Notification {
+    /// Who sends notifications with these alerts, for error messages
+    const SENDER: &'static str;
+
+    /// Version of the payload with these alerts
+    const VERSION: &'static str;
+
+    fn firing(&self) -> bool;
+
+    /// The alert's attachment. `external_url` links to the sender's UI
+    fn attachment(&self, external_url: &str) -> Attachment;
+}
+
+/// A webhook notification for a group of alerts, as sent by Alertmanager, or by Grafana with its own alerts
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payload<A = Alert> {
+    version: String,
+
+    /// Name of the receiver the notification is for
+    pub receiver: String,
+
+    status: String,
+
+    // Alerts left out by the receiver's `max_alerts`
+    #[serde(default)]
+    truncated_alerts: usize,
+
+    #[serde(default)]
+    group_labels: BTreeMap<String, String>,
+
+    #[serde(default)]
+    common_labels: BTreeMap<String, String>,
+
+    #[serde(default, rename = "externalURL")]
+    external_url: String,
+
+    alerts: Vec<A>,
+}
+
+/// An alert sent by Alertmanager
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    status: String,
+
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+
+    #[serde(default)]
+    starts_at: String,
+
+    #[serde(default)]
+    ends_at: String,
+
+    #[serde(default, rename = "generatorURL")]
+    generator_url: String,
+}
+
+impl<A: Notification> Payload<A> {
+    /// Check the payload is a version this understands
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version == A::VERSION {
+            Ok(())
+        } else {
+            Err(format!(
+                "unsupported {} payload version {}, expected {}",
+                A::SENDER,
+                self.version,
+                A::VERSION
+            ))
+        }
+    }
+
+    // The alert name shared by the group, if there is one
+    fn name(&self) -> &str {
+        self.group_labels
+            .get("alertname")
+            .or_else(|| self.common_labels.get("alertname"))
+            .unwrap_or(&self.receiver)
+    }
+
+    /// A message for the group, with an attachment for each of the first `max_alerts` alerts, firing ones first
+    pub fn message(&self, max_alerts: usize) -> Message {
+        let firing = self.alerts.iter().filter(|alert| alert.firing()).count();
+        let mut text = if self.status == FIRING {
+            format!(":fire: *[FIRING:{firing}] {}*", self.name())
+        } else {
+            format!(":white_check_mark: *[RESOLVED] {}*", self.name())
+        };
+        let grouped: Vec<String> = self
+            .group_labels
+            .iter()
+            .filter(|(name, _)| *name != "alertname")
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        if !grouped.is_empty() {
+            let _ = write!(text, " ({})", grouped.join(", "));
+        }
+
+        let mut alerts: Vec<&A> = self.alerts.iter().collect();
+        alerts.sort_by_key(|alert| !alert.firing());
+        let hidden = alerts.len().saturating_sub(max_alerts) + self.truncated_alerts;
+        if hidden > 0 {
+            let _ = write!(text, "\n_and {hidden} more not shown_");
+        }
+
+        Message::new().text(text).attachments(
+            alerts
+                .into_iter()
+                .take(max_alerts)
+                .map(|alert| alert.attachment(&self.external_url))
+                .collect(),
+        )
+    }
+}
+
+impl Notification for Alert {
+    const SENDER: &'static str = "Alertmanager";
+    const VERSION: &'static str = "4";
+
+    fn firing(&self) -> bool {
+        self.status == FIRING
+    }
+
+    fn attachment(&self, external_url: &str) -> Attachment {
+        let mut links = Vec::new();
+        if self.firing() && !external_url.is_empty() {
+            links.push(format!("[Silence]({})", self.silence_url(external_url)));
+        }
+        self.attachment_with(links, Vec::new())
+    }
+}
+
+impl Alert {
+    fn color(&self) -> Color {
+        if !self.firing() {
+            return Color::Success;
+        }
+        let severity = self.labels.get("severity").map(|s| s.to_ascii_lowercase());
+        match severity.as_deref() {
+            Some("warning" | "warn" | "medium") => Color::Warning,
+            Some("info" | "low" | "none") => Color::Info,
+            // Alerts are urgent unless they say otherwise
+            _ => Color::Danger,
+        }
+    }
+
+    // A link to the Alertmanager UI, to silence alerts with the same labels
+    fn silence_url(&self, external_url: &str) -> String {
+        let matchers: Vec<String> = self
+            .labels
+            .iter()
+            .map(|(name, value)| {
+                let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("{name}=\"{value}\"")
+            })
+            .collect();
+        let filter = format!("{{{}}}", matchers.join(","));
+        format!(
+            "{}/#/silences/new?filter={}",
+            external_url.trim_end_matches('/'),
+            urlencoding::encode(&filter)
+        )
+    }
+
+    // When the alert started, or ended if it is resolved. Alertmanager sends the zero time for unset times
+    fn time(&self) -> Option<DateTime<Utc>> {
+        let time = if self.firing() {
+            &self.starts_at
+        } else {
+            &self.ends_at
+        };
+        DateTime::parse_from_rfc3339(time)
+            .ok()
+            .map(|time| time.with_timezone(&Utc))
+            .filter(|time| time.year() > 1)
+    }
+
+    /// The alert's attachment, with `links` after its source, and `fields` before its labels
+    pub fn attachment_with(
+        &self,
+        mut links: Vec<String>,
+        fields: Vec<AttachmentField>,
+    ) -> Attachment {
+        let mut attachment = Attachment::new()
+            .color(self.color())
+            .title(self.labels.get("alertname").map_or("Alert", String::as_str));
+        if !self.generator_url.is_empty() {
+            attachment = attachment.title_link(&self.generator_url);
+            links.insert(0, format!("[Source]({})", self.generator_url));
+        }
+        if let Some(time) = self.time() {
+            attachment = attachment.ts(time);
+        }
+
+        let mut text: Vec<String> = TEXT_ANNOTATIONS
+            .iter()
+            .filter_map(|name| self.annotations.get(*name).cloned())
+            .collect();
+        if !links.is_empty() {
+            text.push(links.join(" | "));
+        }
+
+        let labels = self
+            .labels
+            .iter()
+            .filter(|(name, _)| *name != "alertname")
+            .map(|(name, value)| AttachmentField::new().short(true).title(name).value(value));
+        let annotations = self
+            .annotations
+            .iter()
+            .filter(|(name, _)| !TEXT_ANNOTATIONS.contains(&name.as_str()))
+            .map(|(name, value)| AttachmentField::new().title(name).value(value));
+        attachment.text(text.join("\n")).fields(
+            fields
+                .into_iter()
+                .chain(labels)
+                .chain(annotations)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    // A notification as sent by Alertmanager, for two alerts of a group
+    const FIRING_PAYLOAD: &str = r#"{
+        "receiver": "team-ops",
+        "status": "firing",
+        "alerts": [
+            {
+                "status": "firing",
+                "labels": { "alertname": "HighCPU", "instance": "web-1:9100", "job": "node", "severity": "warning" },
+                "annotations": {
+                    "summary": "CPU above 90%",
+                    "description": "web-1 has been busy for 10m",
+                    "runbook_url": "https://runbooks.example.com/cpu"
+                },
+                "startsAt": "2024-05-01T12:00:00.123Z",
+                "endsAt": "0001-01-01T00:00:00Z",
+                "generatorURL": "http://prometheus:9090/graph?g0.expr=cpu",
+                "fingerprint": "c6b1f4a4c7b5a1b2"
+            },
+            {
+                "status": "resolved",
+                "labels": { "alertname": "HighCPU", "instance": "web-2:9100", "job": "node", "severity": "critical" },
+                "annotations": { "summary": "CPU above 90%" },
+                "startsAt": "2024-05-01T11:00:00Z",
+                "endsAt": "2024-05-01T11:30:00Z",
+                "generatorURL": "",
+                "fingerprint": "d7c2e5b5d8c6b2c3"
+            }
+        ],
+        "groupLabels": { "alertname": "HighCPU" },
+        "commonLabels": { "alertname": "HighCPU", "job": "node" },
+        "commonAnnotations": { "summary": "CPU above 90%" },
+        "externalURL": "http://alertmanager:9093/",
+        "version": "4",
+        "groupKey": "{}:{alertname=\"HighCPU\"}",
+        "truncatedAlerts": 0
+    }"#;
+
+    const RESOLVED_PAYLOAD: &str = r#"{
+        "receiver": "team-ops",
+        "status": "resolved",
+        "alerts": [
+            {
+                "status": "resolved",
+                "labels": { "alertname": "DiskFull", "instance": "db-1:9100", "mountpoint": "/var" },
+                "annotations": { "description": "Less than 5% of /var is free" },
+                "startsAt": "2024-05-01T10:00:00Z",
+                "endsAt": "2024-05-01T10:45:00Z",
+                "generatorURL": "http://prometheus:9090/graph?g0.expr=disk",
+                "fingerprint": "e8d3f6c6e9d7c3d4"
+            }
+        ],
+        "groupLabels": { "alertname": "DiskFull", "instance": "db-1:9100" },
+        "commonLabels": { "alertname": "DiskFull", "instance": "db-1:9100", "mountpoint": "/var" },
+        "commonAnnotations": {},
+        "externalURL": "http://alertmanager:9093",
+        "version": "4",
+        "groupKey": "{}:{alertname=\"DiskFull\", instance=\"db-1:9100\"}",
+        "truncatedAlerts": 0
+    }"#;
+
+    fn payload(json: &str) -> Payload {
+        let payload: Payload = serde_json::from_str(json).unwrap();
+        payload.validate().unwrap();
+        payload
+    }
+
+    fn render(json: &str, max_alerts: usize) -> Value {
+        serde_json::to_value(payload(json).message(max_alerts)).unwrap()
+    }
+
+    fn alert(status: &str, labels: &Value) -> Alert {
+        serde_json::from_value(json!({ "status": status, "labels": labels })).unwrap()
+    }
+
+    #[test]
+    fn firing_message() {
+        let message = render(FIRING_PAYLOAD, MAX_ALERTS);
+        assert_eq!(message["text"], ":fire: *[FIRING:1] HighCPU*");
+
+        let attachments = message["attachments"].as_array().unwrap();
+        assert_eq!(attachments.len(), 2);
+        let firing = &attachments[0];
+        assert_eq!(firing["title"], "HighCPU");
+        assert_eq!(
+            firing["title_link"],
+            "http://prometheus:9090/graph?g0.expr=cpu"
+        );
+        assert_eq!(firing["color"], "#ffd21f");
+        assert_eq!(firing["ts"], "2024-05-01T12:00:00.123Z");
+        assert_eq!(
+            firing["text"],
+            "CPU above 90%\nweb-1 has been busy for 10m\n\
+             [Source](http://prometheus:9090/graph?g0.expr=cpu) | \
+             [Silence](http://alertmanager:9093/#/silences/new?filter=\
+             %7Balertname%3D%22HighCPU%22%2Cinstance%3D%22web-1%3A9100%22%2Cjob%3D%22node%22%2Cseverity%3D%22warning%22%7D)"
+        );
+        assert_eq!(
+            firing["fields"],
+            json!([
+                { "short": true, "title": "instance", "value": "web-1:9100" },
+                { "short": true, "title": "job", "value": "node" },
+                { "short": true, "title": "severity", "value": "warning" },
+                { "title": "runbook_url", "value": "https://runbooks.example.com/cpu" }
+            ])
+        );
+
+        // Resolved alerts in a firing group come after the firing ones, without a link to silence them
+        let resolved = &attachments[1];
+        assert_eq!(resolved["color"], "#2de0a5");
+        assert_eq!(resolved["ts"], "2024-05-01T11:30:00Z");
+        assert_eq!(resolved["text"], "CPU above 90%");
+        assert!(resolved.get("title_link").is_none());
+    }
+
+    #[test]
+    fn resolved_message() {
+        let message = render(RESOLVED_PAYLOAD, MAX_ALERTS);
+        assert_eq!(
+            message["text"],
+            ":white_check_mark: *[RESOLVED] DiskFull* (instance=db-1:9100)"
+        );
+        let attachment = &message["attachments"][0];
+        assert_eq!(attachment["color"], "#2de0a5");
+        assert_eq!(attachment["ts"], "2024-05-01T10:45:00Z");
+        assert_eq!(
+            attachment["text"],
+            "Less than 5% of /var is free\n[Source](http://prometheus:9090/graph?g0.expr=disk)"
+        );
+    }
+
+    #[test]
+    fn alerts_beyond_max_are_counted() {
+        let message = render(FIRING_PAYLOAD, 1);
+        assert_eq!(
+            message["text"],
+            ":fire: *[FIRING:1] HighCPU*\n_and 1 more not shown_"
+        );
+        assert_eq!(message["attachments"].as_array().unwrap().len(), 1);
+        assert_eq!(message["attachments"][0]["title"], "HighCPU");
+
+        let truncated =
+            FIRING_PAYLOAD.replace(r#""truncatedAlerts": 0"#, r#""truncatedAlerts": 3"#);
+        assert_eq!(
+            render(&truncated, MAX_ALERTS)["text"],
+            ":fire: *[FIRING:1] HighCPU*\n_and 3 more not shown_"
+        );
+    }
+
+    #[test]
+    fn severity_colors() {
+        for (severity, color) in [
+            (json!("critical"), Color::Danger),
+            (json!("Warning"), Color::Warning),
+            (json!("medium"), Color::Warning),
+            (json!("info"), Color::Info),
+            (json!("none"), Color::Info),
+            (json!("page"), Color::Danger),
+            (Value::Null, Color::Danger),
+        ] {
+            let labels = match &severity {
+                Value::Null => json!({}),
+                severity => json!({ "severity": severity }),
+            };
+            assert_eq!(
+                alert(FIRING, &labels).color().to_string(),
+                color.to_string(),
+                "{severity}"
+            );
+            assert_eq!(
+                alert("resolved", &labels).color().to_string(),
+                Color::Success.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn silence_links_quote_labels() {
+        let alert = alert(FIRING, &json!({ "alertname": "Up", "path": r#"C:\"x""# }));
+        assert_eq!(
+            alert.silence_url("https://am.example.com/"),
+            "https://am.example.com/#/silences/new?filter=\
+             %7Balertname%3D%22Up%22%2Cpath%3D%22C%3A%5C%5C%5C%22x%5C%22%22%7D"
+        );
+        // Without Alertmanager's URL, there is nothing to link to
+        let attachment = serde_json::to_value(alert.attachment("")).unwrap();
+        assert!(attachment.get("text").is_none(), "{attachment}");
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let payload: Payload =
+            serde_json::from_str(&FIRING_PAYLOAD.replace(r#""version": "4""#, r#""version": "5""#))
+                .unwrap();
+        assert_eq!(
+            payload.validate().unwrap_err(),
+            "unsupported Alertmanager payload version 5, expected 4"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use clap::{ArgMatches, Args};
+use rocketchat::{
+    color::Color,
+    request::{Attachment, AttachmentField},
+};
+use std::{iter, str::FromStr};
+
+use crate::parse_key_value;
+
+/// Options describing attachments. Each `--attachment` starts a new attachment, and the options following it apply
+/// to that attachment. Options before the first `--attachment` apply to the attachment holding the message
+#[derive(Debug, Default, Args)]
+pub struct AttachmentArgs {
+    /// Add another attachment with the given text. Attachment options that follow apply to it
+    #[arg(long = "attachment", value_name = "TEXT", help_heading = "Attachment")]
+    attachments: Vec<String>,
+
+    /// Title of the attachment
+    #[arg(long, value_name = "TITLE", help_heading = "Attachment")]
+    attachment_title: Vec<String>,
+
+    /// Color of the attachment, either a level like 'success', 'info', 'warning', 'danger', or any CSS color
+    #[arg(long, value_name = "COLOR", help_heading = "Attachment")]
+    #[arg(value_parser = Color::from_str)]
+    attachment_color: Vec<Color>,
+
+    /// Add a field to the attachment
+    #[arg(long = "field", value_name = "KEY=VALUE", help_heading = "Attachment")]
+    #[arg(value_parser = parse_key_value)]
+    fields: Vec<(String, String)>,
+
+    /// Add a short field to the attachment, displayed side by side with other short fields
+    #[arg(
+        long = "short-field",
+        value_name = "KEY=VALUE",
+        help_heading = "Attachment"
+    )]
+    #[arg(value_parser = parse_key_value)]
+    short_fields: Vec<(String, String)>,
+
+    /// Name of the attachment author
+    #[arg(long, value_name = "NAME", help_heading = "Attachment")]
+    author: Vec<String>,
+
+    /// Link opened by clicking the attachment author
+    #[arg(long, value_name = "URL", help_heading = "Attachment")]
+    author_link: Vec<String>,
+
+    /// Icon displayed next to the attachment author
+    #[arg(long, value_name = "URL", help_heading = "Attachment")]
+    author_icon: Vec<String>,
+
+    /// Link opened by clicking the attachment title
+    #[arg(long, value_name = "URL", help_heading = "Attachment")]
+    title_link: Vec<String>,
+
+    /// Image displayed in the attachment
+    #[arg(long, value_name = "URL", help_heading = "Attachment")]
+    image: Vec<String>,
+
+    /// Thumbnail displayed next to the attachment text
+    #[arg(long, value_name = "URL", help_heading = "Attachment")]
+    thumb: Vec<String>,
+
+    /// Audio file played in the attachment
+    #[arg(long, value_name = "URL", help_heading = "Attachment")]
+    audio: Vec<String>,
+
+    /// Video file played in the attachment
+    #[arg(long, value_name = "URL", help_heading = "Attachment")]
+    video: Vec<String>,
+}
+
+// Values of a repeated option, along with their position on the command line
+fn indexed<'a, T>(
+    matches: &ArgMatches,
+    id: &str,
+    values: &'a [T],
+) -> impl Iterator<Item = (usize, &'a T)> {
+    matches.indices_of(id).into_iter().flatten().zip(values)
+}
+
+// Attachments, and the command line positions at which each one starts
+struct Groups<F> {
+    attachments: Vec<Option<Attachment>>,
+    starts: Vec<usize>,
+    new: F,
+}
+
+impl<F: Fn(&str) -> Attachment> Groups<F> {
+    // Apply options to the attachment each one follows, adding the message attachment if it is needed
+    fn set<'a, T: 'a, I, S>(&mut self, values: I, set: S)
+    where
+        I: Iterator<Item = (usize, &'a T)>,
+        S: Fn(Attachment, &T) -> Attachment,
+    {
+        for (index, value) in values {
+            let group = &mut self.attachments[self.starts.partition_point(|start| *start < index)];
+            let attachment = group.take().unwrap_or_else(|| (self.new)(""));
+            *group = Some(set(attachment, value));
+        }
+    }
+}
+
+impl AttachmentArgs {
+    /// Whether any attachments are added with `--attachment`
+    pub fn adds_attachments(&self) -> bool {
+        !self.attachments.is_empty()
+    }
+
+    /// Apply the attachment options, to the message attachment `first` and to new attachments created by `new`.
+    /// `first` is created if options apply to it
+    pub fn build<F: Fn(&str) -> Attachment>(
+        &self,
+        matches: &ArgMatches,
+        first: Option<Attachment>,
+        new: F,
+    ) -> Vec<Attachment> {
+        let mut groups = Groups {
+            attachments: iter::once(first)
+                .chain(self.attachments.iter().map(|text| Some(new(text))))
+                .collect(),
+            starts: matches
+                .indices_of("attachments")
+                .into_iter()
+                .flatten()
+                .collect(),
+            new,
+        };
+
+        groups.set(
+            indexed(matches, "attachment_title", &self.attachment_title),
+            |attachment, title| attachment.title(title),
+        );
+        groups.set(
+            indexed(matches, "attachment_color", &self.attachment_color),
+            |attachment, color| attachment.color(color.clone()),
+        );
+
+        // Fields keep the order they were given in, whether short or not
+        let mut fields: Vec<_> = indexed(matches, "fields", &self.fields)
+            .map(|(index, field)| (index, (false, field)))
+            .chain(
+                indexed(matches, "short_fields", &self.short_fields)
+                    .map(|(index, field)| (index, (true, field))),
+            )
+            .collect();
+        fields.sort_by_key(|(index, _)| *index);
+        groups.set(
+            fields.iter().map(|(index, field)| (*index, field)),
+            |attachment, (short, (title, value))| {
+                attachment.field(
+                    AttachmentField::new()
+                        .short(*short)
+                        .title(title)
+                        .value(value),
+                )
+            },
+        );
+
+        groups.set(
+            indexed(matches, "author", &self.author),
+            |attachment, name| attachment.author_name(name),
+        );
+        groups.set(
+            indexed(matches, "author_link", &self.author_link),
+            |attachment, url| attachment.author_link(url),
+        );
+        groups.set(
+            indexed(matches, "author_icon", &self.author_icon),
+            |attachment, url| attachment.author_icon(url),
+        );
+        groups.set(
+            indexed(matches, "title_link", &self.title_link),
+            |attachment, url| attachment.title_link(url),
+        );
+        groups.set(indexed(matches, "image", &self.image), |attachment, url| {
+            attachment.image_url(url)
+        });
+        groups.set(indexed(matches, "thumb", &self.thumb), |attachment, url| {
+            attachment.thumb_url(url)
+        });
+        groups.set(indexed(matches, "audio", &self.audio), |attachment, url| {
+            attachment.audio_url(url)
+        });
+        groups.set(indexed(matches, "video", &self.video), |attachment, url| {
+            attachment.video_url(url)
+        });
+
+        groups.attachments.into_iter().flatten().collect()
+    }
+}
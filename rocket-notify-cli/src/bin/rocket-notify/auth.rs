@@ -0,0 +1,201 @@
+use rocketchat::request::Destination;
+use std::collections::BTreeMap;
+
+use crate::{config, secret};
+
+/// A client of the gateway or receiver, with its API key
+#[derive(Debug)]
+pub struct Caller {
+    pub name: String,
+    key: String,
+    channels: Vec<String>,
+    aliases: Vec<String>,
+}
+
+impl Caller {
+    /// A client with a key given on the command line, which may send anywhere, as anyone
+    pub fn unrestricted<S: Into<String>>(name: &str, key: S) -> Self {
+        Caller {
+            name: name.into(),
+            key: key.into(),
+            channels: vec!["*".into()],
+            aliases: vec!["*".into()],
+        }
+    }
+
+    /// Whether the client may send to `destination`, as named by [`destination_name`]
+    pub fn may_send_to(&self, destination: &str) -> bool {
+        allowed(&self.channels, destination)
+    }
+
+    /// Whether the client may send as `alias`
+    pub fn may_send_as(&self, alias: &str) -> bool {
+        allowed(&self.aliases, alias)
+    }
+}
+
+/// Read the API key of each client, from the config or a file
+pub fn load_callers(keys: &BTreeMap<String, config::ApiKey>) -> Result<Vec<Caller>, String> {
+    keys.iter()
+        .map(|(name, settings)| {
+            let key = match (&settings.key, &settings.key_file) {
+                (Some(key), _) => key.trim().to_string(),
+                (None, Some(path)) => secret::read_file(path, "API key file")?,
+                (None, None) => return Err(format!("API key `{name}` has no `key` or `key_file`")),
+            };
+            if key.is_empty() {
+                return Err(format!("API key `{name}` is empty"));
+            }
+            Ok(Caller {
+                name: name.clone(),
+                key,
+                channels: settings.channels.clone(),
+                aliases: settings.aliases.clone(),
+            })
+        })
+        .collect()
+}
+
+// Compare without stopping at the first difference, so the time taken doesn't reveal how much of a key was guessed
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// Whether `value` matches one of `patterns`, where a trailing `*` matches any suffix
+fn allowed(patterns: &[String], value: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => value.starts_with(prefix),
+            None => pattern == value,
+        })
+}
+
+/// The client whose key is given as a bearer token in `authorization`, the value of the Authorization header
+pub fn authenticate<'a>(callers: &'a [Caller], authorization: Option<&str>) -> Option<&'a Caller> {
+    // The scheme is case insensitive, like 'bearer'
+    let (scheme, token) = authorization?.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Bearer") {
+        return None;
+    }
+    let token = token.trim();
+    callers
+        .iter()
+        .find(|caller| constant_time_eq(caller.key.as_bytes(), token.as_bytes()))
+}
+
+/// The destination clients' channels are matched with, where channels given without their `#` have it added
+pub fn destination_name(destination: &Destination) -> String {
+    match destination {
+        Destination::Channel(channel) if !channel.starts_with('#') => format!("#{channel}"),
+        destination => destination.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn callers() -> Vec<Caller> {
+        let keys = BTreeMap::from([
+            (
+                "billing".to_string(),
+                config::ApiKey {
+                    key: Some("billing-key".into()),
+                    channels: vec!["#billing".into()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "ops".to_string(),
+                config::ApiKey {
+                    key: Some(" ops-key\n".into()),
+                    channels: vec!["*".into()],
+                    ..Default::default()
+                },
+            ),
+        ]);
+        load_callers(&keys).unwrap()
+    }
+
+    #[test]
+    fn allowed_exact() {
+        let patterns = vec!["#ops".to_string(), "@alice".to_string()];
+        assert!(allowed(&patterns, "#ops"));
+        assert!(allowed(&patterns, "@alice"));
+        assert!(!allowed(&patterns, "#ops-team"));
+        assert!(!allowed(&patterns, "#op"));
+        assert!(!allowed(&[], "#ops"));
+    }
+
+    #[test]
+    fn allowed_wildcard() {
+        let patterns = vec!["#team-*".to_string()];
+        assert!(allowed(&patterns, "#team-"));
+        assert!(allowed(&patterns, "#team-ops"));
+        assert!(!allowed(&patterns, "#team"));
+        assert!(!allowed(&patterns, "@team-ops"));
+        assert!(allowed(&["*".to_string()], "anything"));
+    }
+
+    #[test]
+    fn constant_time_eq_compares() {
+        assert!(constant_time_eq(b"key", b"key"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"key", b"kex"));
+        assert!(!constant_time_eq(b"key", b"key2"));
+        assert!(!constant_time_eq(b"key", b""));
+    }
+
+    #[test]
+    fn authenticate_bearer() {
+        let callers = callers();
+        let name = |authorization| authenticate(&callers, authorization).map(|caller| &caller.name);
+        assert_eq!(name(Some("Bearer billing-key")).unwrap(), "billing");
+        // Keys are trimmed in the config, and the scheme is case insensitive
+        assert_eq!(name(Some("bearer ops-key")).unwrap(), "ops");
+        assert_eq!(name(Some("BEARER  ops-key ")).unwrap(), "ops");
+    }
+
+    #[test]
+    fn authenticate_unknown() {
+        let callers = callers();
+        assert!(authenticate(&callers, None).is_none());
+        assert!(authenticate(&callers, Some("")).is_none());
+        assert!(authenticate(&callers, Some("Bearer")).is_none());
+        assert!(authenticate(&callers, Some("Bearer ")).is_none());
+        assert!(authenticate(&callers, Some("Bearer other-key")).is_none());
+        assert!(authenticate(&callers, Some("Bearer billing-ke")).is_none());
+        assert!(authenticate(&callers, Some("Basic billing-key")).is_none());
+        assert!(authenticate(&callers, Some("billing-key")).is_none());
+    }
+
+    #[test]
+    fn load_callers_without_key() {
+        let keys = BTreeMap::from([("ci".to_string(), config::ApiKey::default())]);
+        assert_eq!(
+            load_callers(&keys).unwrap_err(),
+            "API key `ci` has no `key` or `key_file`"
+        );
+        let keys = BTreeMap::from([(
+            "ci".to_string(),
+            config::ApiKey {
+                key: Some(" ".into()),
+                ..Default::default()
+            },
+        )]);
+        assert_eq!(load_callers(&keys).unwrap_err(), "API key `ci` is empty");
+    }
+
+    #[test]
+    fn destination_names() {
+        let name = |destination: &str| destination_name(&destination.parse().unwrap());
+        assert_eq!(name("#ops"), "#ops");
+        assert_eq!(name("ops"), "#ops");
+        assert_eq!(name("@alice"), "@alice");
+        assert_eq!(
+            destination_name(&Destination::RoomId("GENERAL".into())),
+            "GENERAL"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use clap::Args;
+use rocketchat::{
+    color::Color,
+    request::{Attachment, Message},
+};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::{
+    io::{self, BufRead},
+    num::NonZeroUsize,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{CommonArgs, DEFAULT_COLOR, deliver, exit, output, print_err, text};
+
+// How often to check for signals while waiting for input
+const SIGNAL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Args)]
+pub struct BatchArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Title of the messages
+    #[arg(short, long, default_value = "", env = "ROCKET_NOTIFY_TITLE")]
+    title: String,
+
+    /// Color of the message header, either a level like 'success', 'info', 'warning', 'danger', or any CSS color.
+    /// Defaults to darkgrey
+    #[arg(short, long, env = "ROCKET_NOTIFY_COLOR")]
+    #[arg(value_parser = Color::from_str)]
+    color: Option<Color>,
+
+    /// How long to collect lines for, from the first line of a message, like '30s' or '5m'
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "30s",
+        env = "ROCKET_NOTIFY_WINDOW"
+    )]
+    #[arg(value_parser = humantime::parse_duration)]
+    window: Duration,
+
+    /// Most lines to send in a single message, sending early once reached
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "50",
+        env = "ROCKET_NOTIFY_MAX_LINES"
+    )]
+    max_lines: NonZeroUsize,
+
+    #[command(flatten)]
+    format: text::Format,
+
+    /// Channel to which the mesage will be sent, like '#general' or '@eric'. Separate several with commas
+    #[arg(env = "ROCKET_NOTIFY_CHANNEL")]
+    channel: Option<String>,
+}
+
+// Lines collected for the next message, which is due once the window has passed since its first line, or as soon as
+// it has the most lines allowed
+#[derive(Debug)]
+struct Batch {
+    lines: Vec<String>,
+    window: Duration,
+    max_lines: NonZeroUsize,
+    // When the window ends, once there are lines
+    deadline: Option<Instant>,
+}
+
+impl Batch {
+    fn new(window: Duration, max_lines: NonZeroUsize) -> Self {
+        Batch {
+            lines: Vec::with_capacity(max_lines.get()),
+            window,
+            max_lines,
+            deadline: None,
+        }
+    }
+
+    // Add a line received at `now`, returning the lines to send if that fills the batch
+    fn push(&mut self, line: String, now: Instant) -> Option<Vec<String>> {
+        self.deadline.get_or_insert(now + self.window);
+        self.lines.push(line);
+        if self.lines.len() >= self.max_lines.get() {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    // How long to wait for another line at `now`, at most `max`
+    fn timeout(&self, now: Instant, max: Duration) -> Duration {
+        self.deadline.map_or(max, |deadline| {
+            deadline.saturating_duration_since(now).min(max)
+        })
+    }
+
+    // The lines to send if the window has ended by `now`
+    fn expire(&mut self, now: Instant) -> Option<Vec<String>> {
+        if self.deadline.is_some_and(|deadline| now >= deadline) {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    // The lines collected so far, if there are any, starting a new batch
+    fn take(&mut self) -> Option<Vec<String>> {
+        self.deadline = None;
+        if self.lines.is_empty() {
+            None
+        } else {
+            Some(std::mem::replace(
+                &mut self.lines,
+                Vec::with_capacity(self.max_lines.get()),
+            ))
+        }
+    }
+}
+
+// Forward lines from stdin, until it is closed
+fn read_lines(tx: &mpsc::Sender<String>) {
+    let mut stdin = io::stdin().lock();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match stdin.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                let text = String::from_utf8_lossy(&line);
+                if tx
+                    .send(text.trim_end_matches(['\r', '\n']).to_string())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                print_err(format!("failed to read from stdin: {e}"));
+                break;
+            }
+        }
+    }
+}
+
+/// Send lines read from stdin in batches, until it is closed or interrupted. Returns the exit code
+pub fn batch(args: &BatchArgs) -> i32 {
+    let transport = args.common.transport();
+    let (transport, channels) = match transport
+        .and_then(|transport| Ok((transport, args.common.channels(args.channel.as_ref())?)))
+    {
+        Ok(found) => found,
+        Err(e) => {
+            print_err(e);
+            return exit::USAGE;
+        }
+    };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let _ = signal_hook::flag::register(SIGINT, Arc::clone(&stop));
+    let _ = signal_hook::flag::register(SIGTERM, Arc::clone(&stop));
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || read_lines(&tx));
+
+    let color = args
+        .common
+        .color(args.color.as_ref())
+        .unwrap_or(Color::from(DEFAULT_COLOR));
+    // Exit code of the last message that couldn't be sent
+    let mut failed = 0;
+    let mut send = |lines: &[String]| {
+        let attachment = Attachment::new()
+            .text(args.format.apply(&lines.join("\n")))
+            .color(color.clone());
+        let message = args
+            .common
+            .sender(Message::new().text(&args.title).attachment(attachment));
+        for channel in &channels {
+            if let Err(e) = deliver::send(&transport, &message.clone().channel(channel)) {
+                output::failed(Some(channel), &e);
+                failed = exit::code(&e);
+            }
+        }
+    };
+
+    let mut batch = Batch::new(args.window, args.max_lines);
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let full = match rx.recv_timeout(batch.timeout(Instant::now(), SIGNAL_INTERVAL)) {
+            Ok(line) => batch.push(line, Instant::now()),
+            Err(RecvTimeoutError::Timeout) => batch.expire(Instant::now()),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if let Some(lines) = full {
+            send(&lines);
+        }
+    }
+
+    // Lines already read, but not yet received
+    for line in rx.try_iter() {
+        if let Some(lines) = batch.push(line, Instant::now()) {
+            send(&lines);
+        }
+    }
+    if let Some(lines) = batch.take() {
+        send(&lines);
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(max_lines: usize) -> Batch {
+        Batch::new(
+            Duration::from_secs(30),
+            NonZeroUsize::new(max_lines).unwrap(),
+        )
+    }
+
+    #[test]
+    fn window_expires() {
+        let start = Instant::now();
+        let mut batch = batch(50);
+        // Nothing is due before the first line
+        assert_eq!(batch.timeout(start, SIGNAL_INTERVAL), SIGNAL_INTERVAL);
+        assert_eq!(batch.expire(start + Duration::from_mins(1)), None);
+
+        assert_eq!(batch.push("one".into(), start), None);
+        assert_eq!(
+            batch.push("two".into(), start + Duration::from_secs(20)),
+            None
+        );
+        // The window starts with the first line
+        assert_eq!(
+            batch.timeout(start + Duration::from_secs(25), Duration::from_mins(1)),
+            Duration::from_secs(5)
+        );
+        assert_eq!(batch.expire(start + Duration::from_secs(29)), None);
+        assert_eq!(
+            batch.expire(start + Duration::from_secs(30)).unwrap(),
+            ["one", "two"]
+        );
+
+        // The next line starts a new window
+        let later = start + Duration::from_mins(2);
+        assert_eq!(batch.push("three".into(), later), None);
+        assert_eq!(batch.expire(later + Duration::from_secs(29)), None);
+        assert_eq!(
+            batch.expire(later + Duration::from_secs(30)).unwrap(),
+            ["three"]
+        );
+    }
+
+    #[test]
+    fn max_lines_sends_early() {
+        let start = Instant::now();
+        let mut batch = batch(2);
+        assert_eq!(batch.push("one".into(), start), None);
+        assert_eq!(batch.push("two".into(), start).unwrap(), ["one", "two"]);
+        assert_eq!(batch.timeout(start, SIGNAL_INTERVAL), SIGNAL_INTERVAL);
+        assert_eq!(batch.expire(start + Duration::from_mins(1)), None);
+
+        let mut single = self::batch(1);
+        assert_eq!(single.push("one".into(), start).unwrap(), ["one"]);
+        assert_eq!(single.push("two".into(), start).unwrap(), ["two"]);
+    }
+
+    #[test]
+    fn remaining_lines_flushed() {
+        let start = Instant::now();
+        let mut batch = batch(3);
+        assert_eq!(batch.take(), None);
+        for line in ["one", "two", "three", "four"] {
+            let _ = batch.push(line.into(), start);
+        }
+        assert_eq!(batch.take().unwrap(), ["four"]);
+        assert_eq!(batch.take(), None);
+    }
+}
@@ -0,0 +1,181 @@
+use rocketchat::color::Color;
+use serde::{Deserialize, Deserializer, de};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Name of the profile used when none is selected, and the config file sets no `default_profile`
+const DEFAULT_PROFILE: &str = "default";
+
+/// Per-user configuration directory, like `~/.config/rocket-notify`
+pub fn dir() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("rocket-notify"))
+}
+
+// Parse an optional value with its `FromStr` implementation, to validate it like the matching command line option
+fn parse<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+/// Settings used when they are not given by command line options or environment variables
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Rocket.Chat webhook URL
+    pub url: Option<String>,
+
+    /// File to read the webhook URL from, used when `url` isn't set
+    pub url_file: Option<PathBuf>,
+
+    /// Shell command printing the webhook URL, used when `url` and `url_file` aren't set
+    pub url_command: Option<String>,
+
+    /// Channel to send to when none is given
+    pub channel: Option<String>,
+
+    /// Alias for the message sender
+    pub alias: Option<String>,
+
+    /// Emoji for the sender's icon
+    pub icon: Option<String>,
+
+    /// URL of the sender's icon, used instead of `icon`
+    pub avatar: Option<String>,
+
+    /// Color of messages
+    #[serde(deserialize_with = "parse")]
+    pub color: Option<Color>,
+
+    /// Number of times to retry sending a message
+    pub retries: Option<u32>,
+
+    /// Delay before the first retry, like '500ms' or '2s'
+    #[serde(deserialize_with = "parse")]
+    pub retry_delay: Option<humantime::Duration>,
+
+    /// Directory containing named templates
+    pub template_dir: Option<PathBuf>,
+
+    /// Template variables, overridden by those given on the command line
+    pub vars: Map<String, Value>,
+
+    /// Clients allowed to send messages through the gateway, or alerts to the receiver, by name
+    pub keys: BTreeMap<String, ApiKey>,
+
+    /// Channels to send alerts to, by Alertmanager receiver
+    pub routes: BTreeMap<String, String>,
+
+    /// Config file the profile was read from, if there was one
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+
+    /// Name of the profile, if one was selected
+    #[serde(skip)]
+    pub name: Option<String>,
+}
+
+/// A client of the gateway or receiver, with the API key it authenticates with and where it may send messages
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiKey {
+    /// The API key
+    pub key: Option<String>,
+
+    /// File to read the API key from, used when `key` isn't set
+    pub key_file: Option<PathBuf>,
+
+    /// Channels the client may send to, like '#ops' or '@alice'. A trailing '*' matches any suffix, like '#team-*'
+    pub channels: Vec<String>,
+
+    /// Aliases the client may send as, matched like `channels`. Messages without an alias are sent as the
+    /// profile's alias
+    pub aliases: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    default_profile: Option<String>,
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// The config file read when none is given, like `~/.config/rocket-notify/config.toml`
+pub fn default_path() -> Option<PathBuf> {
+    dir().map(|dir| dir.join("config.toml"))
+}
+
+/// Load a profile from the config file at `path`, or the default config file. Selects the profile named `profile`,
+/// otherwise the config file's `default_profile`, otherwise the profile named `default` if there is one
+pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Profile, String> {
+    let explicit = path.is_some();
+    let Some(path) = path.map(Path::to_path_buf).or_else(default_path) else {
+        return match profile {
+            Some(profile) => Err(format!("profile {profile} not found, no config file")),
+            None => Ok(Profile::default()),
+        };
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        // Only a config file given explicitly is required to exist
+        Err(e) if e.kind() == ErrorKind::NotFound && !explicit => return Ok(Profile::default()),
+        Err(e) => {
+            return Err(format!(
+                "failed to read config file {}: {e}",
+                path.display()
+            ));
+        }
+    };
+    let config: Config = toml::from_str(&content)
+        .map_err(|e| format!("failed to parse config file {}: {e}", path.display()))?;
+
+    let name = profile.or(config.default_profile.as_deref());
+    let profile = match name {
+        Some(name) => config.profiles.get(name).cloned().ok_or_else(|| {
+            let names: Vec<_> = config.profiles.keys().map(String::as_str).collect();
+            format!(
+                "profile {name} not found in {}. Available profiles: {}",
+                path.display(),
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            )
+        }),
+        None => Ok(config
+            .profiles
+            .get(DEFAULT_PROFILE)
+            .cloned()
+            .unwrap_or_default()),
+    }?;
+    Ok(Profile {
+        path: Some(path),
+        name: name.map(String::from).or_else(|| {
+            config
+                .profiles
+                .contains_key(DEFAULT_PROFILE)
+                .then(|| DEFAULT_PROFILE.into())
+        }),
+        ..profile
+    })
+}
@@ -0,0 +1,587 @@
+use clap::Args;
+use listenfd::ListenFd;
+use rocketchat::client::ErrorKind;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs::{self, File, TryLockError},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, MetadataExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    CommonArgs,
+    deliver::{self, Transport},
+    exit, output,
+    output::print_warn,
+    print_err,
+};
+
+// Longest message accepted on the socket, in bytes
+const MAX_LINE: u64 = 1024 * 1024;
+
+// How long to wait for the daemon to queue a message
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Delay before sending a message again when the server can't be reached, doubling up to the maximum
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+// Directory in the queue that messages rejected by the server are moved to
+const FAILED_DIR: &str = "failed";
+
+// File in the queue locked by the daemon using it, so a second daemon doesn't send its messages again
+const LOCK_FILE: &str = "lock";
+
+const SOCKET_NAME: &str = "rocket-notify.sock";
+
+#[derive(Debug, Args)]
+pub struct DaemonArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Directory to keep messages in until they are sent. Defaults to 'queue' in the service's state directory
+    /// when run by systemd, otherwise ~/.local/state/rocket-notify/queue
+    #[arg(long, value_name = "DIR", env = "ROCKET_NOTIFY_QUEUE_DIR")]
+    queue_dir: Option<PathBuf>,
+}
+
+/// The socket in `$XDG_RUNTIME_DIR`, or in a directory of the temporary directory private to the user
+pub fn default_socket() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map_or_else(private_dir, PathBuf::from)
+        .join(SOCKET_NAME)
+}
+
+// The directory holding the socket when there is no `$XDG_RUNTIME_DIR`, like `/tmp/rocket-notify-1000`. Other users
+// can create files in the temporary directory, so it is checked before use
+fn private_dir() -> PathBuf {
+    env::temp_dir().join(format!(
+        "rocket-notify-{}",
+        rustix::process::getuid().as_raw()
+    ))
+}
+
+// Check the directory holding `socket` can't be used by other users to intercept messages, if it is the private
+// directory. With `create`, it is created if missing
+fn check_socket_dir(socket: &Path, create: bool) -> Result<(), String> {
+    let dir = private_dir();
+    if socket.parent() != Some(&dir) {
+        return Ok(());
+    }
+    if create {
+        match fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+                return Err(format!("failed to create {}: {e}", dir.display()));
+            }
+            _ => {}
+        }
+    }
+    let metadata = match fs::symlink_metadata(&dir) {
+        Ok(metadata) => metadata,
+        // Connecting reports the missing socket
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !create => return Ok(()),
+        Err(e) => return Err(format!("failed to read {}: {e}", dir.display())),
+    };
+    if !metadata.is_dir() {
+        Err(format!("{} isn't a directory", dir.display()))
+    } else if metadata.uid() != rustix::process::getuid().as_raw() {
+        Err(format!("{} is owned by another user", dir.display()))
+    } else if metadata.mode() & 0o077 != 0 {
+        Err(format!(
+            "{} can be used by other users. Run `chmod 700` on it",
+            dir.display()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn default_queue_dir() -> Option<PathBuf> {
+    // systemd sets this for services with `StateDirectory=`, separating several with colons
+    if let Some(dirs) = env::var_os("STATE_DIRECTORY") {
+        return env::split_paths(&dirs).next().map(|dir| dir.join("queue"));
+    }
+    let dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+    Some(dir.join("rocket-notify").join("queue"))
+}
+
+// The daemon's reply to each message, as a JSON line
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Reply {
+    queued: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Reply {
+    fn error<S: Display>(error: S) -> Self {
+        Reply {
+            error: Some(error.to_string()),
+            ..Reply::default()
+        }
+    }
+}
+
+/// Queue a message with the daemon listening on `socket`, returning its ID once it has been saved
+pub fn queue<T: Serialize + ?Sized>(socket: &Path, payload: &T) -> Result<u64, String> {
+    let context = |e: &dyn Display| {
+        format!(
+            "failed to queue the message with the daemon at {}: {e}",
+            socket.display()
+        )
+    };
+    check_socket_dir(socket, false).map_err(|e| context(&e))?;
+    // Raw payloads may span several lines, so are rewritten on one
+    let mut line = serde_json::to_value(payload)
+        .and_then(|payload| serde_json::to_string(&payload))
+        .map_err(|e| context(&e))?;
+    line.push('\n');
+
+    let mut stream = UnixStream::connect(socket).map_err(|e| context(&e))?;
+    stream
+        .set_read_timeout(Some(REPLY_TIMEOUT))
+        .and_then(|()| stream.write_all(line.as_bytes()))
+        .map_err(|e| context(&e))?;
+    let mut reply = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut reply)
+        .map_err(|e| context(&e))?;
+    if reply.is_empty() {
+        return Err(context(&"the daemon closed the connection"));
+    }
+    let reply: Reply = serde_json::from_str(&reply).map_err(|e| context(&e))?;
+    match (reply.error, reply.id) {
+        (Some(e), _) => Err(context(&e)),
+        (None, Some(id)) => Ok(id),
+        (None, None) => Err(context(
+            &"the daemon didn't say whether the message was queued",
+        )),
+    }
+}
+
+// A message waiting to be sent, saved in the queue directory until it is
+#[derive(Debug)]
+struct Entry {
+    id: u64,
+    payload: Value,
+}
+
+// Messages are saved before they are acknowledged, and only removed once sent, so they are sent at least once even
+// if the daemon is restarted
+#[derive(Debug)]
+struct Queue {
+    dir: PathBuf,
+    next_id: AtomicU64,
+    transport: Transport,
+    // A worker for each channel, sending its messages in the order they were queued
+    workers: Mutex<HashMap<String, mpsc::Sender<Entry>>>,
+    // Held while the queue is open
+    _lock: File,
+}
+
+// The ID of a message saved in `path`
+fn entry_id(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_suffix(".json")?
+        .parse()
+        .ok()
+}
+
+impl Queue {
+    // Open the queue directory, and send any messages left in it, in the order they were queued
+    fn open(dir: PathBuf, transport: Transport) -> Result<Arc<Self>, String> {
+        let context = |e: io::Error| format!("failed to open queue {}: {e}", dir.display());
+        fs::create_dir_all(dir.join(FAILED_DIR)).map_err(context)?;
+        let lock = File::create(dir.join(LOCK_FILE)).map_err(context)?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(format!("another daemon is using queue {}", dir.display()));
+            }
+            Err(TryLockError::Error(e)) => return Err(context(e)),
+        }
+
+        // IDs aren't reused, so failed messages aren't replaced by later ones
+        let mut next_id = 0;
+        for file in fs::read_dir(dir.join(FAILED_DIR)).map_err(context)? {
+            if let Some(id) = entry_id(&file.map_err(context)?.path()) {
+                next_id = next_id.max(id + 1);
+            }
+        }
+
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&dir).map_err(context)? {
+            let path = file.map_err(context)?.path();
+            // Left by a daemon stopped while saving a message, which wasn't acknowledged
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            let Some(id) = entry_id(&path) else {
+                continue;
+            };
+            next_id = next_id.max(id + 1);
+            let payload = fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_slice(&json).map_err(|e| e.to_string()));
+            match payload {
+                Ok(payload) => entries.push(Entry { id, payload }),
+                Err(e) => print_warn(format!("skipping queued message {}: {e}", path.display())),
+            }
+        }
+        entries.sort_by_key(|entry| entry.id);
+
+        let queue = Arc::new(Queue {
+            dir,
+            next_id: AtomicU64::new(next_id),
+            transport,
+            workers: Mutex::default(),
+            _lock: lock,
+        });
+        for entry in entries {
+            queue.dispatch(entry);
+        }
+        Ok(queue)
+    }
+
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{id:020}.json"))
+    }
+
+    // Save a message, then hand it to the worker for its channel
+    fn push(self: &Arc<Self>, payload: Value) -> io::Result<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let path = self.path(id);
+        // Written to another file first, so a partly written message is never sent
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(&payload)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        self.dispatch(Entry { id, payload });
+        Ok(id)
+    }
+
+    fn dispatch(self: &Arc<Self>, entry: Entry) {
+        // Messages without a channel go to the webhook's default channel
+        let channel = entry
+            .payload
+            .get("channel")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let mut workers = self.workers.lock().unwrap();
+        let worker = workers.entry(channel.clone()).or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            let queue = Arc::clone(self);
+            thread::spawn(move || queue.work(&channel, &rx));
+            tx
+        });
+        let _ = worker.send(entry);
+    }
+
+    // Send each message for a channel in turn, waiting for the server while it can't be reached
+    fn work(&self, channel: &str, rx: &mpsc::Receiver<Entry>) {
+        let destination = Some(channel).filter(|channel| !channel.is_empty());
+        for entry in rx {
+            let path = self.path(entry.id);
+            let mut delay = RETRY_DELAY;
+            loop {
+                match deliver::send(&self.transport, &entry.payload) {
+                    Ok(outcome) => {
+                        output::sent(destination, &outcome);
+                        if let Err(e) = fs::remove_file(&path) {
+                            print_warn(format!(
+                                "failed to remove sent message {}: {e}",
+                                path.display()
+                            ));
+                        }
+                    }
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            ErrorKind::Network | ErrorKind::Timeout | ErrorKind::RateLimited
+                        ) =>
+                    {
+                        print_warn(format!(
+                            "failed to send to {}, trying again in {}: {e}",
+                            destination.unwrap_or("the default channel"),
+                            humantime::format_duration(delay)
+                        ));
+                        thread::sleep(delay);
+                        delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY);
+                        continue;
+                    }
+                    Err(e) => {
+                        output::failed(destination, &e);
+                        let failed = self.dir.join(FAILED_DIR).join(path.file_name().unwrap());
+                        if let Err(e) = fs::rename(&path, &failed) {
+                            print_warn(format!("failed to move {}: {e}", path.display()));
+                        }
+                    }
+                }
+                break;
+            }
+        }
+    }
+}
+
+// Queue each JSON line received from a client, replying once it is saved
+fn serve(queue: &Arc<Queue>, stream: &UnixStream) {
+    let mut reader = BufReader::new(stream);
+    let mut writer = stream;
+    loop {
+        let mut line = String::new();
+        match reader.by_ref().take(MAX_LINE).read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let too_long = line.len() as u64 == MAX_LINE && !line.ends_with('\n');
+        let reply = match serde_json::from_str::<Value>(&line) {
+            _ if too_long => Reply::error(format!("message longer than {MAX_LINE} bytes")),
+            Ok(payload) if payload.is_object() => match queue.push(payload) {
+                Ok(id) => Reply {
+                    queued: true,
+                    id: Some(id),
+                    error: None,
+                },
+                Err(e) => Reply::error(format!("failed to save the message: {e}")),
+            },
+            Ok(_) => Reply::error("expected a JSON object"),
+            Err(e) => Reply::error(format!("invalid JSON: {e}")),
+        };
+        let reply = serde_json::to_string(&reply).unwrap_or_default();
+        if writeln!(writer, "{reply}").is_err() || too_long {
+            break;
+        }
+    }
+}
+
+// The socket passed by systemd socket activation, or a new socket bound to `path`. Returns whether it was bound
+fn listen(path: &Path) -> Result<(UnixListener, bool), String> {
+    let activated = ListenFd::from_env()
+        .take_unix_listener(0)
+        .map_err(|e| format!("invalid socket passed by systemd: {e}"))?;
+    if let Some(listener) = activated {
+        listener
+            .set_nonblocking(false)
+            .map_err(|e| format!("invalid socket passed by systemd: {e}"))?;
+        return Ok((listener, false));
+    }
+
+    check_socket_dir(path, true)?;
+    // A socket left by a daemon that didn't stop cleanly is replaced
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{} exists, and isn't a socket", path.display()));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!(
+                "a daemon is already listening on {}",
+                path.display()
+            ));
+        }
+        fs::remove_file(path)
+            .map_err(|e| format!("failed to remove old socket {}: {e}", path.display()))?;
+    }
+    UnixListener::bind(path)
+        .map(|listener| (listener, true))
+        .map_err(|e| format!("failed to listen on {}: {e}", path.display()))
+}
+
+/// Queue messages received on the socket, and send them until interrupted. Returns the exit code
+pub fn daemon(args: &DaemonArgs) -> i32 {
+    if args.common.via_daemon {
+        print_err("the daemon can't send messages via another daemon");
+        return exit::USAGE;
+    }
+    let client = match args.common.client() {
+        Ok(client) => client,
+        Err(e) => {
+            print_err(e);
+            return exit::USAGE;
+        }
+    };
+    let Some(dir) = args.queue_dir.clone().or_else(default_queue_dir) else {
+        print_err("no queue directory. Set one with --queue-dir");
+        return exit::USAGE;
+    };
+    // Listening first fails if another daemon is, before any queued messages are sent
+    let socket = args.common.socket();
+    let (listener, bound) = match listen(&socket) {
+        Ok(listener) => listener,
+        Err(e) => {
+            print_err(e);
+            return exit::USAGE;
+        }
+    };
+    let queue = match Queue::open(dir, Transport::Direct(client)) {
+        Ok(queue) => queue,
+        Err(e) => {
+            print_err(e);
+            if bound {
+                let _ = fs::remove_file(&socket);
+            }
+            return exit::USAGE;
+        }
+    };
+
+    // Remove a socket the daemon created when it stops, so clients fail right away rather than time out
+    if bound {
+        match Signals::new([SIGINT, SIGTERM]) {
+            Ok(mut signals) => {
+                thread::spawn(move || {
+                    if signals.forever().next().is_some() {
+                        let _ = fs::remove_file(&socket);
+                        process::exit(0);
+                    }
+                });
+            }
+            Err(e) => print_warn(format!("failed to handle signals: {e}")),
+        }
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || serve(&queue, &stream));
+            }
+            Err(e) => print_warn(format!("failed to accept a connection: {e}")),
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_server::{FakeServer, SUCCESS, wait_until};
+    use serde_json::json;
+
+    fn save(dir: &Path, name: &str, text: &str) {
+        let payload = json!({ "channel": "#ops", "text": text });
+        fs::write(dir.join(name), serde_json::to_vec(&payload).unwrap()).unwrap();
+    }
+
+    fn pending(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|file| file.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name != FAILED_DIR && name != LOCK_FILE)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn replays_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        save(dir.path(), "00000000000000000003.json", "second");
+        save(dir.path(), "00000000000000000001.json", "first");
+        fs::write(dir.path().join("00000000000000000004.tmp"), "{").unwrap();
+        let server = FakeServer::start(200, SUCCESS);
+
+        let queue = Queue::open(dir.path().into(), Transport::Direct(server.client())).unwrap();
+        let texts: Vec<_> = server
+            .wait_for(2)
+            .iter()
+            .map(|r| r["text"].clone())
+            .collect();
+        assert_eq!(texts, ["first", "second"]);
+        wait_until(|| pending(dir.path()).is_empty());
+        // The partly saved message was never acknowledged, so its ID may be used again
+        assert_eq!(queue.next_id.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn ids_follow_failed_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(FAILED_DIR)).unwrap();
+        save(
+            &dir.path().join(FAILED_DIR),
+            "00000000000000000007.json",
+            "old",
+        );
+        let server = FakeServer::start(200, SUCCESS);
+
+        let queue = Queue::open(dir.path().into(), Transport::Direct(server.client())).unwrap();
+        assert_eq!(queue.push(json!({ "text": "a" })).unwrap(), 8);
+        assert_eq!(queue.push(json!({ "text": "b" })).unwrap(), 9);
+        server.wait_for(2);
+    }
+
+    #[test]
+    fn rejected_messages_are_moved_to_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = FakeServer::start(400, r#"{"success":false,"error":"invalid-channel"}"#);
+        let failed = dir.path().join(FAILED_DIR);
+
+        let queue = Queue::open(dir.path().into(), Transport::Direct(server.client())).unwrap();
+        assert_eq!(
+            queue
+                .push(json!({ "channel": "#nowhere", "text": "a" }))
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            queue
+                .push(json!({ "channel": "#nowhere", "text": "b" }))
+                .unwrap(),
+            1
+        );
+        wait_until(|| failed.join("00000000000000000001.json").exists());
+        assert!(pending(dir.path()).is_empty());
+        // Rejected messages aren't tried again
+        assert_eq!(server.requests().len(), 2);
+        for (name, text) in [
+            ("00000000000000000000.json", "a"),
+            ("00000000000000000001.json", "b"),
+        ] {
+            let payload: Value =
+                serde_json::from_slice(&fs::read(failed.join(name)).unwrap()).unwrap();
+            assert_eq!(payload["text"], text);
+        }
+    }
+
+    #[test]
+    fn one_daemon_per_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        save(dir.path(), "00000000000000000000.json", "once");
+        let server = FakeServer::start(500, "");
+
+        let _queue = Queue::open(dir.path().into(), Transport::Direct(server.client())).unwrap();
+        let e = Queue::open(dir.path().into(), Transport::Direct(server.client())).unwrap_err();
+        assert!(e.starts_with("another daemon is using queue"), "{e}");
+    }
+}
@@ -0,0 +1,171 @@
+use rocketchat::{
+    client::{Client, ClientError, ErrorKind},
+    request::Message,
+    response::Response,
+};
+use serde::Serialize;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{
+    fmt::{self, Display},
+    num::NonZeroUsize,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+#[cfg(unix)]
+use crate::daemon;
+use crate::{exit, output};
+
+/// Where messages are sent
+#[derive(Debug, Clone)]
+pub enum Transport {
+    /// Straight to the Rocket.Chat server
+    Direct(Client),
+
+    /// Queued with the daemon listening on a Unix socket, which sends them on
+    #[cfg(unix)]
+    Daemon(PathBuf),
+
+    /// Nowhere, for a dry run, which doesn't need a server
+    DryRun,
+}
+
+/// What happened to a message that didn't fail
+#[derive(Debug)]
+pub enum Outcome {
+    /// Sent, with the server's response
+    Sent(Box<Response>),
+
+    /// Queued with the daemon, with its ID in the queue
+    Queued(u64),
+
+    /// Printed instead of sent, for a dry run
+    Printed,
+}
+
+/// An error sending a message
+#[derive(Debug)]
+pub enum Error {
+    /// The server couldn't be reached, or didn't accept the message
+    Client(ClientError),
+
+    /// The daemon couldn't be reached, or couldn't queue the message
+    Daemon(String),
+}
+
+impl Error {
+    /// The broad cause of the error. The daemon is treated like an unreachable server
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Client(e) => e.kind(),
+            Error::Daemon(_) => ErrorKind::Network,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Client(e) => e.fmt(f),
+            Error::Daemon(e) => f.write_str(e),
+        }
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Error::Client(e)
+    }
+}
+
+/// Send a payload, or print its JSON instead for a dry run
+pub fn send<T: Serialize + ?Sized>(transport: &Transport, payload: &T) -> Result<Outcome, Error> {
+    if output::dry_run() {
+        println!(
+            "{}",
+            serde_json::to_string(payload).map_err(ClientError::InvalidPayload)?
+        );
+        return Ok(Outcome::Printed);
+    }
+    match transport {
+        Transport::Direct(client) => {
+            Ok(Outcome::Sent(Box::new(client.send_serializable(payload)?)))
+        }
+        #[cfg(unix)]
+        Transport::Daemon(socket) => daemon::queue(socket, payload)
+            .map(Outcome::Queued)
+            .map_err(Error::Daemon),
+        // Printed above
+        Transport::DryRun => Ok(Outcome::Printed),
+    }
+}
+
+/// Send a copy of the message to each destination, using up to `concurrency` requests at once. Reports the outcome
+/// for each destination, in order, and returns the exit code. If sending to every destination fails, the code is
+/// that of the first failure
+pub fn deliver(
+    transport: &Transport,
+    message: &Message,
+    destinations: &[String],
+    concurrency: NonZeroUsize,
+) -> i32 {
+    // Without destinations, the message is sent wherever its payload says
+    if destinations.is_empty() {
+        return match send(transport, message) {
+            Ok(outcome) => {
+                output::sent(None, &outcome);
+                0
+            }
+            Err(e) => {
+                output::failed(None, &e);
+                exit::code(&e)
+            }
+        };
+    }
+
+    let send_to = |destination: &String| send(transport, &message.clone().channel(destination));
+
+    let results: Vec<Result<Outcome, Error>> = if concurrency.get() == 1 {
+        destinations.iter().map(send_to).collect()
+    } else {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(destinations.len()));
+        thread::scope(|scope| {
+            for _ in 0..concurrency.get().min(destinations.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(destination) = destinations.get(index) else {
+                            break;
+                        };
+                        let result = send_to(destination);
+                        results.lock().unwrap().push((index, result));
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    };
+
+    let mut failures = Vec::new();
+    for (destination, result) in destinations.iter().zip(results) {
+        match result {
+            Ok(outcome) => output::sent(Some(destination), &outcome),
+            Err(e) => {
+                output::failed(Some(destination), &e);
+                failures.push(exit::code(&e));
+            }
+        }
+    }
+    match failures.first() {
+        None => 0,
+        Some(code) if failures.len() == destinations.len() => *code,
+        Some(_) => exit::PARTIAL_FAILURE,
+    }
+}
@@ -0,0 +1,56 @@
+use clap::{Args, CommandFactory};
+use clap_complete::Shell;
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crate::{Cli, exit, print_err};
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for
+    shell: Shell,
+}
+
+#[derive(Debug, Args)]
+pub struct ManArgs {
+    /// Write a page for each command to this directory, instead of printing the main page
+    #[arg(long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+}
+
+/// Print shell completions. Returns the exit code
+pub fn completions(args: &CompletionsArgs) -> i32 {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    // Rendered first, since writing to stdout directly panics if it is closed
+    let mut script = Vec::new();
+    clap_complete::generate(args.shell, &mut command, name, &mut script);
+    match io::stdout().write_all(&script) {
+        Ok(()) => 0,
+        Err(e) => {
+            print_err(format!("failed to print completions: {e}"));
+            exit::FAILURE
+        }
+    }
+}
+
+/// Print the man page, or write pages for each command. Returns the exit code
+pub fn man(args: &ManArgs) -> i32 {
+    let command = Cli::command();
+    let result = match &args.out_dir {
+        Some(dir) => clap_mangen::generate_to(command, dir)
+            .map_err(|e| format!("failed to write man pages to {}: {e}", dir.display())),
+        None => clap_mangen::Man::new(command)
+            .render(&mut io::stdout())
+            .map_err(|e| format!("failed to print man page: {e}")),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            print_err(e);
+            exit::FAILURE
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use clap::{ArgMatches, Args};
+use rocketchat::{
+    client::{Client, ClientError, ErrorKind},
+    request::Message,
+    response::ServerInfo,
+    webhook::WebhookUrl,
+};
+use serde_json::json;
+use std::{
+    io::{self, IsTerminal},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{
+    CommonArgs,
+    deliver::{self, Transport},
+    exit, output, secret,
+};
+
+// How long to wait for a TCP connection to the server
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONFIGURATION: &str = "Configuration";
+const WEBHOOK_URL: &str = "Webhook URL";
+const PERMISSIONS: &str = "Secret permissions";
+const DNS: &str = "DNS";
+const TCP: &str = "TCP connection";
+const TLS: &str = "TLS";
+const SERVER: &str = "Server";
+const TEST_MESSAGE: &str = "Test message";
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Also send a test message, to CHANNEL or the profile's channel. Separate several channels with commas
+    #[arg(long, value_name = "CHANNEL", num_args = 0..=1, default_missing_value = "")]
+    send_test: Option<String>,
+}
+
+// The outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+            Status::Skip => "skip",
+        }
+    }
+
+    // A symbol, and the color it is printed in
+    fn symbol(self) -> (&'static str, &'static str) {
+        match self {
+            Status::Pass => ("✓", "0;32"),
+            Status::Warn => ("!", "1;33"),
+            Status::Fail => ("✗", "1;31"),
+            Status::Skip => ("-", "2"),
+        }
+    }
+}
+
+// Prints each check as it completes, remembering which are left and whether any failed
+#[derive(Debug)]
+struct Report {
+    remaining: Vec<&'static str>,
+    failed: bool,
+}
+
+impl Report {
+    fn check<S: AsRef<str>>(&mut self, check: &'static str, status: Status, detail: S) {
+        let detail = detail.as_ref();
+        self.remaining.retain(|remaining| *remaining != check);
+        self.failed |= status == Status::Fail;
+        match output::format() {
+            output::Format::Text => {
+                let (symbol, style) = status.symbol();
+                if output::colored(io::stdout().is_terminal()) {
+                    println!("\x1b[{style}m{symbol}\x1b[m {check}: {detail}");
+                } else {
+                    println!("{symbol} {check}: {detail}");
+                }
+            }
+            output::Format::Json => println!(
+                "{}",
+                json!({ "check": check, "status": status.name(), "detail": detail })
+            ),
+        }
+    }
+
+    // Fail a check that the rest depend on, skipping them. Returns the exit code
+    fn abort<S: AsRef<str>>(&mut self, check: &'static str, detail: S, reason: &str) -> i32 {
+        self.check(check, Status::Fail, detail);
+        for remaining in std::mem::take(&mut self.remaining) {
+            self.check(remaining, Status::Skip, reason);
+        }
+        exit::FAILURE
+    }
+}
+
+/// Check the configuration, and each step of reaching the server. Returns the exit code
+pub fn doctor(args: &mut DoctorArgs, matches: &ArgMatches) -> i32 {
+    let mut report = Report {
+        remaining: vec![
+            CONFIGURATION,
+            WEBHOOK_URL,
+            PERMISSIONS,
+            DNS,
+            TCP,
+            TLS,
+            SERVER,
+        ],
+        failed: false,
+    };
+    if args.send_test.is_some() {
+        report.remaining.push(TEST_MESSAGE);
+    }
+
+    let common = &mut args.common;
+    if let Err(e) = common.configure(matches) {
+        return report.abort(CONFIGURATION, e, "configuration failed");
+    }
+    check_config(&mut report, common);
+
+    let client = match common.client() {
+        Ok(client) => client,
+        Err(e) => return report.abort(WEBHOOK_URL, e, "no valid webhook URL"),
+    };
+    let url = client.url();
+    let source = common
+        .url_source
+        .as_ref()
+        .unwrap_or(&secret::Source::Option);
+    report.check(WEBHOOK_URL, Status::Pass, format!("{url} from {source}"));
+    check_permissions(&mut report, common, source);
+
+    let addrs: Vec<SocketAddr> = match (url.host(), url.port()).to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(e) => return report.abort(DNS, format!("{}: {e}", url.host()), "host not resolved"),
+    };
+    let resolved: Vec<String> = addrs.iter().map(|addr| addr.ip().to_string()).collect();
+    report.check(
+        DNS,
+        Status::Pass,
+        format!("{} resolves to {}", url.host(), resolved.join(", ")),
+    );
+
+    let server = format!("{}:{}", url.host(), url.port());
+    if let Err(e) = connect(&addrs) {
+        return report.abort(TCP, format!("{server}: {e}"), "server not reachable");
+    }
+    report.check(TCP, Status::Pass, format!("connected to {server}"));
+
+    let info = client.server_info();
+    if let Err(e) = check_tls(&mut report, url, &info) {
+        return report.abort(TLS, e.to_string(), "TLS handshake failed");
+    }
+    match info {
+        Ok(info) => report.check(
+            SERVER,
+            Status::Pass,
+            match info.version() {
+                Some(version) => format!("Rocket.Chat {version}"),
+                None => "Rocket.Chat, version hidden".to_string(),
+            },
+        ),
+        Err(e) => report.check(
+            SERVER,
+            Status::Fail,
+            format!("{} doesn't look like a Rocket.Chat server: {e}", url.base()),
+        ),
+    }
+
+    if let Some(channel) = &args.send_test {
+        // Without a channel, the test message goes to the profile's
+        let channel = Some(channel).filter(|channel| !channel.is_empty());
+        test_message(&mut report, &client, &args.common, channel);
+    }
+
+    if report.failed { exit::FAILURE } else { 0 }
+}
+
+fn check_config(report: &mut Report, common: &CommonArgs) {
+    let settings = &common.settings;
+    let detail = match (&settings.path, &settings.name) {
+        (Some(path), Some(name)) => format!("profile `{name}` from {}", path.display()),
+        (Some(path), None) => format!("{} has no default profile", path.display()),
+        (None, _) => "no config file".to_string(),
+    };
+    report.check(CONFIGURATION, Status::Pass, detail);
+}
+
+// Files holding the webhook URL should only be readable by their owner
+fn check_permissions(report: &mut Report, common: &CommonArgs, source: &secret::Source) {
+    let path = match source {
+        secret::Source::File(path) => Some(path),
+        secret::Source::Profile => common.settings.path.as_ref(),
+        _ => None,
+    };
+    let Some(path) = path else {
+        return report.check(PERMISSIONS, Status::Skip, "URL not read from a file");
+    };
+    match secret::permission_problem(path) {
+        Some(problem) => report.check(PERMISSIONS, Status::Warn, problem),
+        None => report.check(
+            PERMISSIONS,
+            Status::Pass,
+            format!("{} is only readable by its owner", path.display()),
+        ),
+    }
+}
+
+// Connect to the first address that accepts a connection
+fn connect(addrs: &[SocketAddr]) -> io::Result<()> {
+    let mut error = io::Error::new(io::ErrorKind::NotFound, "no addresses");
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT) {
+            Ok(_) => return Ok(()),
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+// Any response from the server means the TLS handshake succeeded
+fn check_tls<'a>(
+    report: &mut Report,
+    url: &WebhookUrl,
+    info: &'a Result<ServerInfo, ClientError>,
+) -> Result<(), &'a ClientError> {
+    if !url.base().starts_with("https://") {
+        report.check(
+            TLS,
+            Status::Warn,
+            "the URL uses http, so messages are sent unencrypted",
+        );
+        return Ok(());
+    }
+    match info {
+        Err(e)
+            if e.status().is_none()
+                && matches!(e.kind(), ErrorKind::Network | ErrorKind::Timeout) =>
+        {
+            Err(e)
+        }
+        _ => {
+            report.check(TLS, Status::Pass, "handshake succeeded");
+            Ok(())
+        }
+    }
+}
+
+fn test_message(
+    report: &mut Report,
+    client: &Client,
+    common: &CommonArgs,
+    channel: Option<&String>,
+) {
+    let channels = match common.channels(channel) {
+        Ok(channels) => channels,
+        Err(e) => return report.check(TEST_MESSAGE, Status::Fail, e),
+    };
+    let message = common
+        .sender(Message::new())
+        .text("Test message from rocket-notify doctor");
+    for channel in channels {
+        let message = message.clone().channel(&channel);
+        match deliver::send(&Transport::Direct(client.clone()), &message) {
+            Ok(_) if output::dry_run() => report.check(
+                TEST_MESSAGE,
+                Status::Skip,
+                format!("printed instead of sending to {channel}"),
+            ),
+            Ok(_) => report.check(TEST_MESSAGE, Status::Pass, format!("sent to {channel}")),
+            Err(e) => report.check(
+                TEST_MESSAGE,
+                Status::Fail,
+                format!("failed to send to {channel}: {e}"),
+            ),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use rocketchat::client::ErrorKind;
+
+use crate::deliver;
+
+/// Exit code for other failures, like a watched file that can't be read
+pub const FAILURE: i32 = 1;
+
+/// Exit code for invalid options or configuration, or an input file that can't be read. Also used by clap
+pub const USAGE: i32 = 2;
+
+/// Exit code when the message was sent to some destinations, but not others
+pub const PARTIAL_FAILURE: i32 = 3;
+
+/// Exit code when the server couldn't be reached, or was unavailable
+pub const NETWORK: i32 = 4;
+
+/// Exit code when the server took too long to respond
+pub const TIMEOUT: i32 = 5;
+
+/// Exit code when the server rejected the message
+pub const REJECTED: i32 = 6;
+
+/// Exit code when the server is rate limiting messages
+pub const RATE_LIMITED: i32 = 7;
+
+/// Exit code for an error sending a message
+pub fn code(error: &deliver::Error) -> i32 {
+    match error.kind() {
+        ErrorKind::InvalidUrl => USAGE,
+        ErrorKind::Network => NETWORK,
+        ErrorKind::Timeout => TIMEOUT,
+        ErrorKind::Rejected => REJECTED,
+        ErrorKind::RateLimited => RATE_LIMITED,
+    }
+}
@@ -0,0 +1,71 @@
+// A stand-in for a Rocket.Chat webhook, for tests of code that sends messages
+
+use rocketchat::client::Client;
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Response, Server};
+
+pub const SUCCESS: &str = r#"{"success":true}"#;
+
+/// A server answering every request with the same response, and keeping the body of each request
+pub struct FakeServer {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeServer {
+    pub fn start(status: u16, body: &'static str) -> Self {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/id/token", server.server_addr());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut text = String::new();
+                let _ = request.as_reader().read_to_string(&mut text);
+                received.lock().unwrap().push(text);
+                let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+                let response = Response::from_string(body)
+                    .with_status_code(status)
+                    .with_header(header);
+                let _ = request.respond(response);
+            }
+        });
+        FakeServer { url, requests }
+    }
+
+    pub fn client(&self) -> Client {
+        Client::new(self.url.as_str()).unwrap()
+    }
+
+    /// The bodies of the requests received so far, as JSON
+    pub fn requests(&self) -> Vec<serde_json::Value> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|body| serde_json::from_str(body).unwrap())
+            .collect()
+    }
+
+    /// The bodies of the first `count` requests, waiting for them to arrive
+    pub fn wait_for(&self, count: usize) -> Vec<serde_json::Value> {
+        wait_until(|| self.requests.lock().unwrap().len() >= count);
+        self.requests()
+    }
+}
+
+/// Wait for `done`, which is checked every few milliseconds, panicking if it takes too long
+pub fn wait_until<F: FnMut() -> bool>(mut done: F) {
+    let start = Instant::now();
+    while !done() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "timed out waiting"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,271 @@
+use clap::Args;
+use rocketchat::request::Message;
+use serde_json::Value;
+use tiny_http::Request;
+
+use crate::{
+    CommonArgs,
+    auth::{self, Caller},
+    deliver::Transport,
+    exit,
+    http::{self, Failure, ListenArgs},
+    print_err,
+};
+
+// Path messages are posted to
+const MESSAGES_PATH: &str = "/v1/messages";
+
+#[derive(Debug, Args)]
+pub struct GatewayArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    #[command(flatten)]
+    listen: ListenArgs,
+}
+
+#[derive(Debug)]
+struct Gateway {
+    transport: Transport,
+    callers: Vec<Caller>,
+    // Channel for messages without one
+    channel: Option<String>,
+    // Alias for messages without one
+    alias: String,
+}
+
+impl Gateway {
+    fn handle(&self, request: &mut Request) -> Result<(u16, Value), Failure> {
+        http::post(request, &[MESSAGES_PATH])?;
+        let caller = http::authenticate(request, &self.callers)?;
+        let message: Message = http::read_json(request, "invalid_message")?;
+        let (message, destination) = self.authorize(caller, message)?;
+        http::send(&self.transport, &message, &destination)
+    }
+
+    // Check the client may send the message, filling in the profile's channel and alias. Returns the message with
+    // where it is sent
+    fn authorize(
+        &self,
+        caller: &Caller,
+        mut message: Message,
+    ) -> Result<(Message, String), Failure> {
+        // Messages without a channel go to the profile's, which the client must also be allowed to use
+        if message.get_destination().is_none() {
+            let channel = self
+                .channel
+                .as_ref()
+                .ok_or_else(|| Failure::new(400, "invalid_message", "no channel given"))?;
+            message = message.channel(channel);
+        }
+        let destination = message
+            .get_destination()
+            .map(auth::destination_name)
+            .unwrap_or_default();
+        http::single_channel(&destination).map_err(|e| Failure::new(400, "invalid_message", e))?;
+        if !caller.may_send_to(&destination) {
+            return Err(Failure::new(
+                403,
+                "forbidden",
+                format!("{} may not send to {destination}", caller.name),
+            ));
+        }
+        if message.get_alias().is_empty() {
+            message = message.alias(&self.alias);
+        } else if !caller.may_send_as(message.get_alias()) {
+            return Err(Failure::new(
+                403,
+                "forbidden",
+                format!("{} may not send as {}", caller.name, message.get_alias()),
+            ));
+        }
+        Ok((message, destination))
+    }
+}
+
+/// Accept messages over HTTP from clients with API keys, and send them on until interrupted. Returns the exit code
+pub fn gateway(args: &GatewayArgs) -> i32 {
+    let setup = args.common.transport().and_then(|transport| {
+        let channel = args.common.channel(None).ok();
+        if let Some(channel) = &channel {
+            http::single_channel(channel)
+                .map_err(|e| format!("invalid `channel` in the profile: {e}"))?;
+        }
+        if args.common.settings.keys.is_empty() {
+            return Err(
+                "no API keys configured. Add clients to `keys` in the config file profile".into(),
+            );
+        }
+        Ok(Gateway {
+            transport,
+            callers: auth::load_callers(&args.common.settings.keys)?,
+            channel,
+            alias: args.common.alias.clone(),
+        })
+    });
+    let gateway = match setup {
+        Ok(gateway) => gateway,
+        Err(e) => {
+            print_err(e);
+            return exit::USAGE;
+        }
+    };
+    http::serve(&args.listen, |request| gateway.handle(request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ApiKey;
+    use rocketchat::{client::Client, request::Destination};
+    use std::collections::BTreeMap;
+
+    fn gateway(channel: Option<&str>) -> Gateway {
+        let keys = BTreeMap::from([
+            (
+                "billing".to_string(),
+                ApiKey {
+                    key: Some("billing-key".into()),
+                    channels: vec!["#billing".into(), "#finance-*".into()],
+                    aliases: vec!["Billing".into()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "ops".to_string(),
+                ApiKey {
+                    key: Some("ops-key".into()),
+                    channels: vec!["*".into()],
+                    aliases: vec!["*".into()],
+                    ..Default::default()
+                },
+            ),
+        ]);
+        Gateway {
+            transport: Transport::Direct(Client::new("http://127.0.0.1:1/hooks/id/token").unwrap()),
+            callers: auth::load_callers(&keys).unwrap(),
+            channel: channel.map(String::from),
+            alias: "Notifications".into(),
+        }
+    }
+
+    fn caller<'a>(gateway: &'a Gateway, key: &str) -> &'a Caller {
+        auth::authenticate(&gateway.callers, Some(&format!("Bearer {key}"))).unwrap()
+    }
+
+    // The status of the failure authorizing a message, or where it is sent and as whom
+    fn authorize(gateway: &Gateway, key: &str, message: &str) -> Result<(String, String), u16> {
+        let message: Message = serde_json::from_str(message).unwrap();
+        gateway
+            .authorize(caller(gateway, key), message)
+            .map(|(message, destination)| (destination, message.get_alias().to_string()))
+            .map_err(|failure| failure.status)
+    }
+
+    #[test]
+    fn allowed_channels() {
+        let gateway = gateway(None);
+        for channel in ["#billing", "billing", "#finance-eu"] {
+            let message = format!(r#"{{"channel": "{channel}", "text": "hi"}}"#);
+            assert!(
+                authorize(&gateway, "billing-key", &message).is_ok(),
+                "{channel}"
+            );
+        }
+        assert_eq!(
+            authorize(&gateway, "ops-key", r##"{"channel": "#anything"}"##).unwrap(),
+            ("#anything".into(), "Notifications".into())
+        );
+    }
+
+    #[test]
+    fn denied_channels() {
+        let gateway = gateway(None);
+        for channel in ["#ops", "#billing-eu", "#finance", "@alice"] {
+            let message = format!(r#"{{"channel": "{channel}", "text": "hi"}}"#);
+            assert_eq!(
+                authorize(&gateway, "billing-key", &message),
+                Err(403),
+                "{channel}"
+            );
+        }
+        // A list could sneak channels past a wildcard
+        assert_eq!(
+            authorize(
+                &gateway,
+                "billing-key",
+                r##"{"channel": "#finance-eu,#ops"}"##
+            ),
+            Err(400)
+        );
+    }
+
+    #[test]
+    fn default_channel_checked() {
+        let message = r#"{"text": "hi"}"#;
+        assert_eq!(
+            authorize(&gateway(Some("#billing")), "billing-key", message).unwrap(),
+            ("#billing".into(), "Notifications".into())
+        );
+        assert_eq!(
+            authorize(&gateway(Some("#ops")), "billing-key", message),
+            Err(403)
+        );
+        assert_eq!(authorize(&gateway(None), "billing-key", message), Err(400));
+    }
+
+    #[test]
+    fn room_ids() {
+        let gateway = gateway(None);
+        let message = r#"{"roomId": "GENERAL", "text": "hi"}"#;
+        assert_eq!(authorize(&gateway, "billing-key", message), Err(403));
+        assert_eq!(
+            authorize(&gateway, "ops-key", message).unwrap().0,
+            "GENERAL"
+        );
+        let message: Message = serde_json::from_str(message).unwrap();
+        let (message, _) = gateway
+            .authorize(caller(&gateway, "ops-key"), message)
+            .unwrap();
+        assert_eq!(
+            message.get_destination(),
+            Some(&Destination::RoomId("GENERAL".into()))
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        let gateway = gateway(None);
+        let message = |alias: &str| format!(r##"{{"channel": "#billing", "alias": "{alias}"}}"##);
+        assert_eq!(
+            authorize(&gateway, "billing-key", &message("Billing"))
+                .unwrap()
+                .1,
+            "Billing"
+        );
+        assert_eq!(
+            authorize(&gateway, "billing-key", &message("Ops")),
+            Err(403)
+        );
+        assert_eq!(
+            authorize(&gateway, "ops-key", &message("Ops")).unwrap().1,
+            "Ops"
+        );
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let gateway = gateway(Some("#billing"));
+        for authorization in [
+            None,
+            Some("Bearer unknown"),
+            Some("Bearer "),
+            Some("Token ops-key"),
+        ] {
+            assert!(
+                auth::authenticate(&gateway.callers, authorization).is_none(),
+                "{authorization:?}"
+            );
+        }
+    }
+}
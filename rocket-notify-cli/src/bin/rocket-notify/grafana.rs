@@ -0,0 +1,225 @@
+use rocketchat::request::{Attachment, AttachmentField, Message};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    alertmanager::{self, MAX_ALERTS, Notification},
+    payload::open_input,
+};
+
+/// A Grafana unified alerting webhook notification
+pub type Payload = alertmanager::Payload<Alert>;
+
+/// An alert sent by Grafana, which adds links to its dashboard and panel, a rendered image, and the queried values
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    // The fields shared with Alertmanager
+    #[serde(flatten)]
+    base: alertmanager::Alert,
+
+    #[serde(default, rename = "silenceURL")]
+    silence_url: String,
+
+    #[serde(default, rename = "dashboardURL")]
+    dashboard_url: String,
+
+    #[serde(default, rename = "panelURL")]
+    panel_url: String,
+
+    #[serde(default, rename = "imageURL")]
+    image_url: String,
+
+    // Values of the rule's queries and expressions, by reference ID like 'B'
+    #[serde(default)]
+    values: Option<BTreeMap<String, Value>>,
+
+    // The values as a single string, sent by older versions instead of `values`
+    #[serde(default)]
+    value_string: String,
+}
+
+impl Notification for Alert {
+    const SENDER: &'static str = "Grafana";
+    const VERSION: &'static str = "1";
+
+    fn firing(&self) -> bool {
+        self.base.firing()
+    }
+
+    fn attachment(&self, _external_url: &str) -> Attachment {
+        let mut links = Vec::new();
+        for (name, url) in [
+            ("Dashboard", &self.dashboard_url),
+            ("Panel", &self.panel_url),
+        ] {
+            if !url.is_empty() {
+                links.push(format!("[{name}]({url})"));
+            }
+        }
+        if self.firing() && !self.silence_url.is_empty() {
+            links.push(format!("[Silence]({})", self.silence_url));
+        }
+
+        let mut fields: Vec<AttachmentField> = self
+            .values
+            .iter()
+            .flatten()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                AttachmentField::new().short(true).title(name).value(value)
+            })
+            .collect();
+        if fields.is_empty() && !self.value_string.is_empty() {
+            fields.push(
+                AttachmentField::new()
+                    .title("Values")
+                    .value(&self.value_string),
+            );
+        }
+
+        let mut attachment = self.base.attachment_with(links, fields);
+        // The panel shows the alert in context, so is linked rather than the rule
+        if let Some(url) = [&self.panel_url, &self.dashboard_url]
+            .into_iter()
+            .find(|url| !url.is_empty())
+        {
+            attachment = attachment.title_link(url);
+        }
+        if !self.image_url.is_empty() {
+            attachment = attachment.image_url(&self.image_url);
+        }
+        attachment
+    }
+}
+
+/// Read a Grafana notification from a file, where '-' is stdin, and render it as a message
+pub fn read_message(path: &Path) -> Result<Message, String> {
+    let payload: Payload = serde_json::from_reader(open_input(path)?)
+        .map_err(|e| format!("failed to parse Grafana alert from {}: {e}", path.display()))?;
+    payload
+        .validate()
+        .map_err(|e| format!("{e}, in {}", path.display()))?;
+    Ok(payload.message(MAX_ALERTS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // A Grafana notification for a group with a firing and a resolved alert
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/grafana.json");
+
+    fn render(path: &Path) -> Value {
+        serde_json::to_value(read_message(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn firing_alert() {
+        let message = render(Path::new(FIXTURE));
+        assert_eq!(message["text"], ":fire: *[FIRING:1] High memory usage*");
+        let firing = &message["attachments"][0];
+        assert_eq!(firing["title"], "High memory usage");
+        assert_eq!(
+            firing["title_link"],
+            "https://grafana.example.com/d/memory?orgId=1&viewPanel=2"
+        );
+        assert_eq!(firing["color"], "#f5455c");
+        assert_eq!(firing["ts"], "2024-05-01T10:00:00.157Z");
+        assert_eq!(
+            firing["image_url"],
+            "https://grafana.example.com/public/img/attachments/memory.png"
+        );
+        assert_eq!(
+            firing["text"],
+            "Memory usage is high in zone us-1\nThe system has high memory usage\n\
+             [Source](https://grafana.example.com/alerting/grafana/eds7ue4y/view?orgId=1) | \
+             [Dashboard](https://grafana.example.com/d/memory?orgId=1) | \
+             [Panel](https://grafana.example.com/d/memory?orgId=1&viewPanel=2) | \
+             [Silence](https://grafana.example.com/alerting/silence/new?alertmanager=grafana\
+             &matcher=alertname%3DHigh+memory+usage&matcher=team%3Dblue&matcher=zone%3Dus-1)"
+        );
+        // The values come first, then the labels and other annotations
+        assert_eq!(
+            firing["fields"],
+            json!([
+                { "short": true, "title": "B", "value": "94.25" },
+                { "short": true, "title": "C", "value": "1" },
+                { "short": true, "title": "team", "value": "blue" },
+                { "short": true, "title": "zone", "value": "us-1" },
+                { "title": "runbook_url", "value": "https://runbooks.example.com/memory" }
+            ])
+        );
+    }
+
+    #[test]
+    fn resolved_alert() {
+        let message = render(Path::new(FIXTURE));
+        let resolved = &message["attachments"][1];
+        assert_eq!(resolved["color"], "#2de0a5");
+        assert_eq!(resolved["ts"], "2024-05-01T09:30:00Z");
+        // Without a panel, the dashboard is linked. There is no link to silence a resolved alert
+        assert_eq!(
+            resolved["title_link"],
+            "https://grafana.example.com/d/memory?orgId=1"
+        );
+        assert_eq!(
+            resolved["text"],
+            "Memory usage is high in zone us-2\n\
+             [Source](https://grafana.example.com/alerting/grafana/eds7ue4y/view?orgId=1) | \
+             [Dashboard](https://grafana.example.com/d/memory?orgId=1)"
+        );
+        assert!(resolved.get("image_url").is_none());
+        // Without `values`, the value string is shown
+        assert_eq!(
+            resolved["fields"][0],
+            json!({ "title": "Values", "value": "[ var='B' labels={zone=us-2} value=71.5 ]" })
+        );
+    }
+
+    #[test]
+    fn resolved_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("resolved.json");
+        let mut payload: Value =
+            serde_json::from_str(&std::fs::read_to_string(FIXTURE).unwrap()).unwrap();
+        payload["status"] = "resolved".into();
+        payload["alerts"][1]["status"] = "resolved".into();
+        payload["alerts"][1]["endsAt"] = "2024-05-01T12:30:00+02:00".into();
+        std::fs::write(&path, payload.to_string()).unwrap();
+
+        let message = render(&path);
+        assert_eq!(
+            message["text"],
+            ":white_check_mark: *[RESOLVED] High memory usage*"
+        );
+        for attachment in message["attachments"].as_array().unwrap() {
+            assert_eq!(attachment["color"], "#2de0a5");
+            assert!(
+                !attachment["text"].as_str().unwrap().contains("[Silence]"),
+                "{attachment}"
+            );
+        }
+    }
+
+    #[test]
+    fn alertmanager_payloads_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alertmanager.json");
+        std::fs::write(
+            &path,
+            r#"{"version": "4", "receiver": "ops", "status": "firing", "alerts": []}"#,
+        )
+        .unwrap();
+        let e = read_message(&path).unwrap_err();
+        assert!(
+            e.starts_with("unsupported Grafana payload version 4, expected 1, in "),
+            "{e}"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use clap::Args;
+use rocketchat::{client::ErrorKind, request::Message};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{io::Read, thread};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    auth::{self, Caller},
+    deliver::{self, Outcome, Transport},
+    exit, output,
+    output::print_warn,
+    print_err,
+};
+
+// Largest request body accepted, in bytes
+const MAX_BODY: u64 = 1024 * 1024;
+
+// Number of requests handled at once
+const WORKERS: usize = 8;
+
+/// Options for commands serving HTTP
+#[derive(Debug, Args)]
+pub struct ListenArgs {
+    /// Address to listen on, like '127.0.0.1:8080', or ':8080' for all interfaces
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
+    #[arg(env = "ROCKET_NOTIFY_LISTEN")]
+    listen: String,
+}
+
+impl ListenArgs {
+    // `:8080` listens on all interfaces
+    fn address(&self) -> String {
+        if self.listen.starts_with(':') {
+            format!("0.0.0.0{}", self.listen)
+        } else {
+            self.listen.clone()
+        }
+    }
+}
+
+/// A request that couldn't be handled, with the status to respond with, and a `kind` clients can match on
+#[derive(Debug)]
+pub struct Failure {
+    pub status: u16,
+    pub kind: &'static str,
+    message: String,
+    // Messages that couldn't be sent are reported when sending them
+    reported: bool,
+}
+
+impl Failure {
+    pub fn new<S: Into<String>>(status: u16, kind: &'static str, message: S) -> Self {
+        Failure {
+            status,
+            kind,
+            message: message.into(),
+            reported: false,
+        }
+    }
+}
+
+/// The path of the request, without any query
+pub fn path(request: &Request) -> &str {
+    request.url().split('?').next().unwrap_or_default()
+}
+
+/// Check the request is a POST to one of `paths`, returning the one it was to
+pub fn post<'a>(request: &Request, paths: &[&'a str]) -> Result<&'a str, Failure> {
+    let path = path(request);
+    let Some(found) = paths.iter().find(|known| **known == path) else {
+        return Err(Failure::new(
+            404,
+            "not_found",
+            format!("no such path {path}"),
+        ));
+    };
+    if *request.method() != Method::Post {
+        return Err(Failure::new(
+            405,
+            "method_not_allowed",
+            format!("expected POST {found}"),
+        ));
+    }
+    Ok(found)
+}
+
+/// The client whose API key the request was sent with, as a bearer token
+pub fn authenticate<'a>(request: &Request, callers: &'a [Caller]) -> Result<&'a Caller, Failure> {
+    let authorization = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .map(|header| header.value.as_str());
+    auth::authenticate(callers, authorization)
+        .ok_or_else(|| Failure::new(401, "unauthorized", "missing or unknown API key"))
+}
+
+/// Read a JSON request body, failing with `kind` if it isn't valid
+pub fn read_json<T: DeserializeOwned>(
+    request: &mut Request,
+    kind: &'static str,
+) -> Result<T, Failure> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| Failure::new(400, kind, format!("failed to read the body: {e}")))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(Failure::new(
+            413,
+            "too_large",
+            format!("body longer than {MAX_BODY} bytes"),
+        ));
+    }
+    serde_json::from_slice(&body).map_err(|e| Failure::new(400, kind, format!("invalid body: {e}")))
+}
+
+/// Check `channel` isn't a comma separated list. Each request is answered with the outcome of one message, so
+/// messages received over HTTP are only sent to one channel
+pub fn single_channel(channel: &str) -> Result<(), String> {
+    if channel.contains(',') {
+        Err(format!(
+            "{channel} lists several channels, but messages received over HTTP are sent to one"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Send a message, reporting the outcome, and return the response for the request
+pub fn send(
+    transport: &Transport,
+    message: &Message,
+    destination: &str,
+) -> Result<(u16, Value), Failure> {
+    match deliver::send(transport, message) {
+        Ok(outcome) => {
+            output::sent(Some(destination), &outcome);
+            let status = if matches!(outcome, Outcome::Queued(_)) {
+                202
+            } else {
+                200
+            };
+            Ok((status, output::outcome_json(Some(destination), &outcome)))
+        }
+        Err(e) => {
+            output::failed(Some(destination), &e);
+            let status = match e.kind() {
+                ErrorKind::InvalidUrl => 500,
+                ErrorKind::Network => 502,
+                ErrorKind::Timeout => 504,
+                ErrorKind::Rejected => 422,
+                ErrorKind::RateLimited => 429,
+            };
+            Err(Failure {
+                reported: true,
+                ..Failure::new(status, output::kind(&e), e.to_string())
+            })
+        }
+    }
+}
+
+fn respond(request: Request, result: Result<(u16, Value), Failure>) {
+    let (status, body) = match result {
+        Ok(reply) => reply,
+        Err(failure) => {
+            if !failure.reported {
+                let from = request
+                    .remote_addr()
+                    .map_or("unknown address".into(), ToString::to_string);
+                print_warn(format!("rejected request from {from}: {}", failure.message));
+            }
+            let body = output::error_json(None, failure.kind, &failure.message);
+            (failure.status, body)
+        }
+    };
+    let mut response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    if status == 401 {
+        response.add_header(Header::from_bytes("WWW-Authenticate", "Bearer").unwrap());
+    }
+    if let Err(e) = request.respond(response) {
+        print_warn(format!("failed to respond: {e}"));
+    }
+}
+
+/// Handle requests until interrupted. Returns the exit code
+pub fn serve<F>(args: &ListenArgs, handle: F) -> i32
+where
+    F: Fn(&mut Request) -> Result<(u16, Value), Failure> + Sync,
+{
+    let address = args.address();
+    let server = match Server::http(&address) {
+        Ok(server) => server,
+        Err(e) => {
+            print_err(format!("failed to listen on {address}: {e}"));
+            return exit::USAGE;
+        }
+    };
+    thread::scope(|scope| {
+        for _ in 0..WORKERS {
+            scope.spawn(|| {
+                for mut request in server.incoming_requests() {
+                    let result = handle(&mut request);
+                    respond(request, result);
+                }
+            });
+        }
+    });
+    0
+}
@@ -0,0 +1,852 @@
+use chrono::{DateTime, Utc};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, parser::ValueSource};
+use rocketchat::{
+    client::Client,
+    color::Color,
+    request::{Attachment, Message},
+};
+use std::{
+    io::{self, IsTerminal, Read},
+    num::NonZeroUsize,
+    path::PathBuf,
+    process,
+    str::FromStr,
+    sync::LazyLock,
+    time::Duration,
+};
+
+use crate::{
+    output::print_err,
+    payload::{PayloadFormat, read_blocks, read_payload, read_raw_payload},
+    template::Vars,
+};
+
+mod alertmanager;
+mod attachment;
+mod auth;
+mod batch;
+mod config;
+#[cfg(unix)]
+mod daemon;
+mod deliver;
+mod docs;
+mod doctor;
+mod exit;
+#[cfg(test)]
+mod fake_server;
+mod gateway;
+mod grafana;
+mod http;
+mod output;
+mod payload;
+mod receiver;
+mod run;
+mod secret;
+mod template;
+mod text;
+mod watch;
+
+// Color of messages when none is given, darkgrey
+const DEFAULT_COLOR: &str = "#a9a9a9";
+
+static HOSTNAME: LazyLock<String> = LazyLock::new(|| {
+    hostname::get()
+        .unwrap_or("rocket-notify".into())
+        .to_string_lossy()
+        .replace(".local", "")
+});
+
+#[derive(Debug, Parser)]
+#[command(name = "rocket-notify", version, about, long_about, author)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    send: Args,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Send a message. This is the default when no command is given
+    Send(Box<Args>),
+
+    /// Run a command, and send a message with its outcome
+    Run(run::RunArgs),
+
+    /// Send lines read from stdin, batched into periodic messages
+    Batch(batch::BatchArgs),
+
+    /// Follow a log file, and send lines matching a pattern
+    Watch(watch::WatchArgs),
+
+    /// Queue messages received on a Unix socket, and send them to the server
+    #[cfg(unix)]
+    Daemon(daemon::DaemonArgs),
+
+    /// Accept messages over HTTP from clients with API keys, and send them to the server
+    Gateway(gateway::GatewayArgs),
+
+    /// Receive alerts from Alertmanager or Grafana, and send them as messages
+    Receiver(receiver::ReceiverArgs),
+
+    /// Check the configuration and connection to the server
+    Doctor(doctor::DoctorArgs),
+
+    /// Print shell completions
+    Completions(docs::CompletionsArgs),
+
+    /// Print the man page
+    Man(docs::ManArgs),
+}
+
+/// Options shared by all commands that send messages
+#[derive(Debug, clap::Args)]
+struct CommonArgs {
+    /// Rocket.Chat webhook URL
+    #[arg(short, long, env = "ROCKET_NOTIFY_URL", hide_env_values = true)]
+    #[arg(conflicts_with_all = ["url_file", "url_command"])]
+    url: Option<String>,
+
+    /// Read the webhook URL from the first line of a file, keeping it out of the process list and shell history
+    #[arg(long, value_name = "FILE", env = "ROCKET_NOTIFY_URL_FILE")]
+    #[arg(conflicts_with = "url_command")]
+    url_file: Option<PathBuf>,
+
+    /// Read the webhook URL from the first line printed by a shell command, like 'pass show rocket'
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        env = "ROCKET_NOTIFY_URL_COMMAND",
+        hide_env_values = true
+    )]
+    url_command: Option<String>,
+
+    /// Alias for the message sender
+    #[arg(short, long, default_value_t = HOSTNAME.clone(), env = "ROCKET_NOTIFY_ALIAS")]
+    alias: String,
+
+    /// Set the sender's icon to an emoji
+    #[arg(short, long, default_value = ":computer:", env = "ROCKET_NOTIFY_ICON")]
+    #[arg(value_name = "EMOJI", conflicts_with = "avatar")]
+    icon: String,
+
+    /// Set the sender's icon to the provided URL
+    #[arg(short = 'A', long = "avatar", env = "ROCKET_NOTIFY_AVATAR")]
+    #[arg(value_name = "URL", conflicts_with = "icon")]
+    avatar: Option<String>,
+
+    /// Number of times to retry sending a message if the server can't be reached. Defaults to 0
+    #[arg(long, value_name = "N", env = "ROCKET_NOTIFY_RETRIES")]
+    retries: Option<u32>,
+
+    /// Delay before the first retry, doubling for each retry after it, like '500ms' or '2s'. Defaults to 1s
+    #[arg(long, value_name = "DURATION", env = "ROCKET_NOTIFY_RETRY_DELAY")]
+    #[arg(value_parser = humantime::parse_duration)]
+    retry_delay: Option<Duration>,
+
+    /// Config file to read profiles from. Defaults to ~/.config/rocket-notify/config.toml
+    #[arg(long, value_name = "FILE", env = "ROCKET_NOTIFY_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Profile to use from the config file
+    #[arg(long, value_name = "NAME", env = "ROCKET_NOTIFY_PROFILE")]
+    profile: Option<String>,
+
+    /// Queue messages with a running `rocket-notify daemon`, instead of sending them to the server
+    #[arg(long, env = "ROCKET_NOTIFY_VIA_DAEMON")]
+    via_daemon: bool,
+
+    /// Unix socket the daemon listens on. Defaults to $XDG_RUNTIME_DIR/rocket-notify.sock, or to a directory in /tmp
+    /// private to the user when that isn't set
+    #[arg(long, value_name = "PATH", env = "ROCKET_NOTIFY_SOCKET")]
+    socket: Option<PathBuf>,
+
+    #[command(flatten)]
+    output: output::OutputArgs,
+
+    #[arg(skip)]
+    settings: config::Profile,
+
+    #[arg(skip)]
+    url_source: Option<secret::Source>,
+}
+
+impl CommonArgs {
+    // Fill in settings not given by options or environment variables from the config file profile
+    fn configure(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        let settings = config::load(self.config.as_deref(), self.profile.as_deref())?;
+        self.apply(matches, settings, secret::read_credential)
+    }
+
+    // Fill in settings from `settings`, reading the systemd credential with `credential` if it is needed
+    fn apply<F>(
+        &mut self,
+        matches: &ArgMatches,
+        settings: config::Profile,
+        credential: F,
+    ) -> Result<(), String>
+    where
+        F: FnOnce() -> Result<Option<(String, secret::Source)>, String>,
+    {
+        // Options and environment variables come first, then a systemd credential, then the profile
+        let url = match secret::url(
+            self.url.as_ref(),
+            self.url_file.as_deref(),
+            self.url_command.as_deref(),
+            secret::Source::Option,
+        )? {
+            Some(found) => Some(found),
+            None => match credential()? {
+                Some(found) => Some(found),
+                None => secret::url(
+                    settings.url.as_ref(),
+                    settings.url_file.as_deref(),
+                    settings.url_command.as_deref(),
+                    secret::Source::Profile,
+                )?,
+            },
+        };
+        (self.url, self.url_source) = url.unzip();
+        if let Some(alias) = settings
+            .alias
+            .as_ref()
+            .filter(|_| !is_set(matches, "alias"))
+        {
+            self.alias.clone_from(alias);
+        }
+        // An icon or avatar given explicitly replaces both of the profile's
+        if !is_set(matches, "icon") && self.avatar.is_none() {
+            if let Some(icon) = &settings.icon {
+                self.icon.clone_from(icon);
+            }
+            self.avatar.clone_from(&settings.avatar);
+        }
+        self.retries = self.retries.or(settings.retries);
+        self.retry_delay = self.retry_delay.or(settings.retry_delay.map(Into::into));
+        self.settings = settings;
+        Ok(())
+    }
+
+    fn client(&self) -> Result<Client, String> {
+        let url = self.url.as_ref().ok_or(
+            "ROCKET_NOTIFY_URL not set.\nPlease acquire a webhook url from an admin, and then\n  `export ROCKET_NOTIFY_URL=https://{webhookURL}`\nor use `--url-file` or `--url-command`, or set `url` in a config file profile".to_string(),
+        )?;
+        let client = Client::new(url)
+            .map_err(|e| e.to_string())?
+            .retries(self.retries.unwrap_or_default());
+        Ok(match self.retry_delay {
+            Some(delay) => client.retry_delay(delay),
+            None => client,
+        })
+    }
+
+    // Where to send messages: the daemon if asked, otherwise the server. Dry runs need neither
+    fn transport(&self) -> Result<deliver::Transport, String> {
+        if output::dry_run() {
+            return Ok(deliver::Transport::DryRun);
+        }
+        if self.via_daemon {
+            #[cfg(unix)]
+            return Ok(deliver::Transport::Daemon(self.socket()));
+            #[cfg(not(unix))]
+            return Err("--via-daemon is only supported on Unix".into());
+        }
+        self.client().map(deliver::Transport::Direct)
+    }
+
+    // The daemon's socket
+    #[cfg(unix)]
+    fn socket(&self) -> PathBuf {
+        self.socket.clone().unwrap_or_else(daemon::default_socket)
+    }
+
+    // The channel to send to, falling back to the profile's
+    fn channel(&self, channel: Option<&String>) -> Result<String, String> {
+        channel.or(self.settings.channel.as_ref()).cloned().ok_or(
+            "no channel given. Pass a channel, or set `channel` in a config file profile".into(),
+        )
+    }
+
+    // The channels to send to, from a comma separated list, falling back to the profile's
+    fn channels(&self, channel: Option<&String>) -> Result<Vec<String>, String> {
+        let channels = split_channels(&self.channel(channel)?);
+        if channels.is_empty() {
+            return Err("no channel given, only commas".into());
+        }
+        Ok(channels)
+    }
+
+    // The message color, falling back to the profile's
+    fn color(&self, color: Option<&Color>) -> Option<Color> {
+        color.or(self.settings.color.as_ref()).cloned()
+    }
+
+    // Set the alias and icon of the message sender
+    fn sender(&self, message: Message) -> Message {
+        let message = message.alias(&self.alias);
+        if let Some(avatar) = &self.avatar {
+            message.avatar(avatar)
+        } else {
+            message.emoji(&self.icon)
+        }
+    }
+}
+
+// Split a comma separated list of channels, ignoring empty entries
+fn split_channels(channels: &str) -> Vec<String> {
+    channels
+        .split(',')
+        .map(str::trim)
+        .filter(|channel| !channel.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Debug, clap::Args)]
+struct Args {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Title of the message
+    #[arg(short, long, default_value = "", env = "ROCKET_NOTIFY_TITLE")]
+    title: String,
+
+    /// Color of the message header, either a level like 'success', 'info', 'warning', 'danger', or any CSS color.
+    /// Defaults to darkgrey
+    #[arg(short, long, env = "ROCKET_NOTIFY_COLOR")]
+    #[arg(value_parser = Color::from_str)]
+    color: Option<Color>,
+
+    /// Send with message block collapsed
+    #[arg(short, long, env = "ROCKET_NOTIFY_MINIMIZE")]
+    minimize: bool,
+
+    /// Time displayed on the message, as RFC 3339 or Unix seconds. Defaults to now
+    #[arg(long, value_name = "TIME", env = "ROCKET_NOTIFY_TIMESTAMP")]
+    #[arg(value_parser = parse_timestamp, conflicts_with = "no_timestamp")]
+    timestamp: Option<DateTime<Utc>>,
+
+    /// Send without a message time
+    #[arg(long, env = "ROCKET_NOTIFY_NO_TIMESTAMP")]
+    no_timestamp: bool,
+
+    #[command(flatten)]
+    format: text::Format,
+
+    #[command(flatten)]
+    attachment: attachment::AttachmentArgs,
+
+    /// JSON file containing an array of layout blocks to add to the message
+    #[arg(long, value_name = "FILE", env = "ROCKET_NOTIFY_BLOCKS")]
+    blocks: Option<PathBuf>,
+
+    /// JSON, YAML or TOML file containing a full message to send. Use '-' for stdin. Other options override the
+    /// fields read from the file, and a message argument adds another attachment
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "ROCKET_NOTIFY_PAYLOAD",
+        conflicts_with = "raw_payload"
+    )]
+    payload: Option<PathBuf>,
+
+    /// Template rendering a message payload file, either a path or the name of a template in the template directory
+    #[arg(long, value_name = "TEMPLATE", env = "ROCKET_NOTIFY_TEMPLATE")]
+    #[arg(conflicts_with_all = ["payload", "raw_payload"])]
+    template: Option<String>,
+
+    /// Set a template variable
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_key_value, requires = "template")]
+    vars: Vec<(String, String)>,
+
+    /// JSON file containing an object of template variables. Use '-' for stdin
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "ROCKET_NOTIFY_VARS_JSON",
+        requires = "template"
+    )]
+    vars_json: Option<PathBuf>,
+
+    /// Format of the payload file. Inferred from the file extension, otherwise YAML (which also accepts JSON)
+    #[arg(long, value_name = "FORMAT", env = "ROCKET_NOTIFY_PAYLOAD_FORMAT")]
+    payload_format: Option<PayloadFormat>,
+
+    /// Grafana alert notification to send as a message, as posted to a webhook contact point. Use '-' for stdin
+    #[arg(long, value_name = "FILE", env = "ROCKET_NOTIFY_FROM_GRAFANA")]
+    #[arg(conflicts_with_all = ["payload", "template", "raw_payload"])]
+    from_grafana: Option<PathBuf>,
+
+    /// JSON file to post verbatim instead of a message, for webhooks with custom scripts. Use '-' for stdin
+    #[arg(long, value_name = "FILE", env = "ROCKET_NOTIFY_RAW_PAYLOAD")]
+    raw_payload: Option<PathBuf>,
+
+    /// Send to up to N destinations at once
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        default_value = "1",
+        env = "ROCKET_NOTIFY_CONCURRENCY"
+    )]
+    concurrency: NonZeroUsize,
+
+    /// Channel to which the mesage will be sent, like '#general' or '@eric'. Separate several with commas, like
+    /// '#ops,@eric'. When the profile has a channel, a single argument is the message instead. A channel named like a
+    /// command, like 'run', needs its '#', or to come after `send`
+    #[arg(env = "ROCKET_NOTIFY_CHANNEL")]
+    channel: Option<String>,
+
+    /// Message to send. Read from stdin if '-', or if omitted and stdin is not a terminal
+    #[arg(env = "ROCKET_NOTIFY_MESSAGE")]
+    message: Option<String>,
+}
+
+fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(secs) = s.parse::<i64>() {
+        return DateTime::from_timestamp(secs, 0).ok_or(format!("timestamp out of range: {secs}"));
+    }
+    DateTime::parse_from_rfc3339(s)
+        .map(|ts| ts.to_utc())
+        .map_err(|e| format!("expected RFC 3339 or Unix seconds: {e}"))
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or(format!("expected KEY=VALUE: {s}"))
+}
+
+fn render_template(template: &str, args: &Args) -> Result<Message, String> {
+    let settings = &args.common.settings;
+    let mut vars = Vars::from(settings.vars.clone());
+    if let Some(path) = &args.vars_json {
+        vars.extend(Vars::from_json(path)?);
+    }
+    for (key, value) in &args.vars {
+        vars.insert(key, value.as_str());
+    }
+    let template_dir = settings
+        .template_dir
+        .clone()
+        .or_else(|| config::dir().map(|dir| dir.join("templates")));
+    template::render(template, template_dir.as_deref(), vars, args.payload_format)
+}
+
+// Whether an argument was explicitly provided, rather than falling back to its default value
+fn is_set(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .value_source(id)
+        .is_some_and(|source| source != ValueSource::DefaultValue)
+}
+
+fn apply_attachment_args(mut attachment: Attachment, args: &Args) -> Attachment {
+    if let Some(color) = &args.color {
+        attachment = attachment.color(color.clone());
+    }
+    if args.minimize {
+        attachment = attachment.collapsed(true);
+    }
+    if let Some(ts) = args.timestamp {
+        attachment = attachment.ts(ts);
+    } else if args.no_timestamp {
+        attachment = attachment.without_ts();
+    }
+    attachment
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut text = String::new();
+    io::stdin()
+        .read_to_string(&mut text)
+        .map_err(|e| format!("failed to read message from stdin: {e}"))?;
+    Ok(text)
+}
+
+// The message text, from the message argument or stdin
+fn read_message(args: &Args) -> Result<Option<String>, String> {
+    match args.message.as_deref() {
+        Some("-") => read_stdin().and_then(|text| {
+            if text.is_empty() {
+                Err("no message given, stdin was empty".into())
+            } else {
+                Ok(Some(text))
+            }
+        }),
+        Some(text) => Ok(Some(text.to_string())),
+        None if args.payload.is_some()
+            || args.template.is_some()
+            || args.from_grafana.is_some() =>
+        {
+            Ok(None)
+        }
+        None if io::stdin().is_terminal() && !args.attachment.adds_attachments() => {
+            Err("no message given. Pass a message, or pipe one to stdin".into())
+        }
+        None if io::stdin().is_terminal() => Ok(None),
+        None => {
+            let text = read_stdin()?;
+            if !text.is_empty() {
+                Ok(Some(text))
+            } else if args.attachment.adds_attachments() {
+                Ok(None)
+            } else {
+                Err("no message given. Pass a message, or pipe one to stdin".into())
+            }
+        }
+    }
+}
+
+fn build_message(args: &Args, matches: &ArgMatches) -> Result<Message, String> {
+    let file_message = if let Some(path) = &args.payload {
+        Some(read_payload(path, args.payload_format)?)
+    } else if let Some(template) = &args.template {
+        Some(render_template(template, args)?)
+    } else if let Some(path) = &args.from_grafana {
+        Some(args.common.sender(grafana::read_message(path)?))
+    } else {
+        None
+    };
+
+    // Options with defaults only override the payload file when explicitly set
+    let from_payload = file_message.is_some();
+
+    let mut message = if let Some(mut message) = file_message {
+        for attachment in message.attachments_mut() {
+            *attachment = apply_attachment_args(std::mem::take(attachment), args);
+        }
+        message
+    } else {
+        Message::new()
+    };
+
+    let new_attachment = |text: &str| {
+        let color = args.common.color(args.color.as_ref());
+        let attachment = Attachment::new()
+            .text(text)
+            .color(color.unwrap_or(Color::from(DEFAULT_COLOR)));
+        apply_attachment_args(attachment, args)
+    };
+    let first = read_message(args)?.map(|text| {
+        new_attachment(&args.format.apply(&text)).title(if args.minimize {
+            &args.title
+        } else {
+            ""
+        })
+    });
+    for attachment in args.attachment.build(matches, first, new_attachment) {
+        message = message.attachment(attachment);
+    }
+    if !from_payload || is_set(matches, "title") {
+        message = message.text(&args.title);
+    }
+    if let Some(path) = &args.blocks {
+        message = message.blocks(read_blocks(path)?);
+    }
+
+    let common = &args.common;
+    if from_payload {
+        if is_set(matches, "alias") {
+            message = message.alias(&common.alias);
+        }
+        if let Some(avatar) = &common.avatar {
+            message = message.avatar(avatar);
+        } else if is_set(matches, "icon") {
+            message = message.emoji(&common.icon);
+        }
+    } else {
+        message = common.sender(message);
+    }
+    Ok(message)
+}
+
+// With a channel in the profile, a single argument is the message to send there, rather than the channel. Payloads
+// may set their own channel, so with them it stays the channel
+fn message_from_channel(args: &mut Args, matches: &ArgMatches) {
+    let from_file = args.payload.is_some()
+        || args.template.is_some()
+        || args.from_grafana.is_some()
+        || args.raw_payload.is_some();
+    if args.message.is_none()
+        && !from_file
+        && args.common.settings.channel.is_some()
+        && matches.value_source("channel") == Some(ValueSource::CommandLine)
+    {
+        args.message = args.channel.take();
+    }
+}
+
+fn send(args: &mut Args, matches: &ArgMatches) {
+    message_from_channel(args, matches);
+    let transport = args.common.transport().unwrap_or_else(|e| {
+        print_err(e);
+        process::exit(exit::USAGE);
+    });
+
+    if let Some(path) = &args.raw_payload {
+        // The payload is posted as it is, so it can't be sent anywhere else. The environment's channel and message
+        // are meant for other messages, so are ignored
+        if ["channel", "message"]
+            .iter()
+            .any(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        {
+            print_err(
+                "a channel or message can't be given with --raw-payload, which is posted as it is",
+            );
+            process::exit(exit::USAGE);
+        }
+        let payload = read_raw_payload(path).unwrap_or_else(|e| {
+            print_err(e);
+            process::exit(exit::USAGE);
+        });
+        match deliver::send(&transport, &*payload) {
+            Ok(outcome) => output::sent(None, &outcome),
+            Err(e) => {
+                output::failed(None, &e);
+                process::exit(exit::code(&e));
+            }
+        }
+        return;
+    }
+
+    let message = build_message(args, matches).unwrap_or_else(|e| {
+        print_err(e);
+        process::exit(exit::USAGE);
+    });
+    let destinations = match args.common.channel(args.channel.as_ref()) {
+        Ok(channel) => split_channels(&channel),
+        // The payload may say where to send the message
+        Err(_) if args.payload.is_some() || args.template.is_some() => Vec::new(),
+        Err(e) => {
+            print_err(e);
+            process::exit(exit::USAGE);
+        }
+    };
+    process::exit(deliver::deliver(
+        &transport,
+        &message,
+        &destinations,
+        args.concurrency,
+    ));
+}
+
+fn main() {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Commands which don't send messages
+    match (&mut cli.command, matches.subcommand()) {
+        (Some(Command::Completions(args)), _) => process::exit(docs::completions(args)),
+        (Some(Command::Man(args)), _) => process::exit(docs::man(args)),
+        // Configuration errors are reported as failed checks
+        (Some(Command::Doctor(args)), Some((_, matches))) => {
+            output::init(args.common.output);
+            process::exit(doctor::doctor(args, matches));
+        }
+        _ => {}
+    }
+
+    let (common, command_matches) = match (&mut cli.command, matches.subcommand()) {
+        (Some(Command::Send(args)), Some((_, matches))) => (&mut args.common, matches),
+        (Some(Command::Run(args)), Some((_, matches))) => (&mut args.common, matches),
+        (Some(Command::Batch(args)), Some((_, matches))) => (&mut args.common, matches),
+        (Some(Command::Watch(args)), Some((_, matches))) => (&mut args.common, matches),
+        #[cfg(unix)]
+        (Some(Command::Daemon(args)), Some((_, matches))) => (&mut args.common, matches),
+        (Some(Command::Gateway(args)), Some((_, matches))) => (&mut args.common, matches),
+        (Some(Command::Receiver(args)), Some((_, matches))) => (&mut args.common, matches),
+        _ => (&mut cli.send.common, &matches),
+    };
+    output::init(common.output);
+    if let Err(e) = common.configure(command_matches) {
+        print_err(e);
+        process::exit(exit::USAGE);
+    }
+
+    match cli.command {
+        Some(Command::Send(mut args)) => send(&mut args, command_matches),
+        Some(Command::Run(args)) => process::exit(run::run(&args)),
+        Some(Command::Batch(args)) => process::exit(batch::batch(&args)),
+        Some(Command::Watch(args)) => process::exit(watch::watch(&args)),
+        #[cfg(unix)]
+        Some(Command::Daemon(args)) => process::exit(daemon::daemon(&args)),
+        Some(Command::Gateway(args)) => process::exit(gateway::gateway(&args)),
+        Some(Command::Receiver(args)) => process::exit(receiver::receiver(&args)),
+        // Sending is the default, for compatibility with versions without commands
+        Some(Command::Doctor(_) | Command::Completions(_) | Command::Man(_)) | None => {
+            send(&mut cli.send, &matches);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Command;
+    use std::{env, fs};
+
+    const CONFIG: &str = r##"
+default_profile = "ops"
+
+[profiles.ops]
+url = "https://profile.example.com/hooks/a/b"
+channel = "#ops"
+alias = "Profile"
+icon = ":robot:"
+retries = 2
+
+[profiles.other]
+url = "https://other.example.com/hooks/c/d"
+"##;
+
+    const CREDENTIAL: &str = "https://credential.example.com/hooks/e/f";
+
+    // The command line, reading the options in `env` from the variables given, rather than their usual ones, so
+    // tests don't depend on the environment
+    fn command(env: &'static [(&str, &'static str)]) -> Command {
+        Cli::command().mut_args(|arg| {
+            let var = env.iter().find(|(id, _)| arg.get_id() == id);
+            arg.env(var.map(|(_, var)| *var))
+        })
+    }
+
+    // The send arguments parsed from `args`, configured from CONFIG, with `credential` whether a systemd credential
+    // is found
+    fn configure(command: Command, args: &[&str], credential: bool) -> Result<Args, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, CONFIG).unwrap();
+        let matches = command
+            .try_get_matches_from(
+                ["rocket-notify", "--config", path.to_str().unwrap()]
+                    .iter()
+                    .chain(args),
+            )
+            .unwrap();
+        let mut args = Cli::from_arg_matches(&matches).unwrap().send;
+        let settings = config::load(
+            args.common.config.as_deref(),
+            args.common.profile.as_deref(),
+        )?;
+        args.common.apply(&matches, settings, || {
+            Ok(credential.then(|| {
+                let path = "/run/credentials/rocket-notify.service/url";
+                (
+                    CREDENTIAL.to_string(),
+                    secret::Source::Credential(path.into()),
+                )
+            }))
+        })?;
+        message_from_channel(&mut args, &matches);
+        Ok(args)
+    }
+
+    fn url(args: &Args) -> (&str, String) {
+        (
+            args.common.url.as_deref().unwrap(),
+            format!("{:?}", args.common.url_source.as_ref().unwrap()),
+        )
+    }
+
+    #[test]
+    fn profile_fallback() {
+        let args = configure(command(&[]), &[], false).unwrap();
+        assert_eq!(
+            url(&args),
+            ("https://profile.example.com/hooks/a/b", "Profile".into())
+        );
+        assert_eq!(args.common.alias, "Profile");
+        assert_eq!(args.common.icon, ":robot:");
+        assert_eq!(args.common.retries, Some(2));
+        assert_eq!(args.common.channel(None).unwrap(), "#ops");
+
+        let args = configure(command(&[]), &["--profile", "other"], false).unwrap();
+        assert_eq!(
+            args.common.url.as_deref().unwrap(),
+            "https://other.example.com/hooks/c/d"
+        );
+        assert_eq!(args.common.alias, *HOSTNAME);
+        assert!(args.common.channel(None).is_err());
+
+        let e = configure(command(&[]), &["--profile", "missing"], false).unwrap_err();
+        assert!(e.starts_with("profile missing not found in "), "{e}");
+    }
+
+    #[test]
+    fn options_override_profile() {
+        let args = [
+            "--url",
+            "https://option.example.com/hooks/x/y",
+            "--alias",
+            "Option",
+            "--avatar",
+            "https://example.com/avatar.png",
+            "--retries",
+            "0",
+            "#general",
+            "hi",
+        ];
+        let args = configure(command(&[]), &args, true).unwrap();
+        assert_eq!(
+            url(&args),
+            ("https://option.example.com/hooks/x/y", "Option".into())
+        );
+        assert_eq!(args.common.alias, "Option");
+        // An avatar replaces the profile's icon
+        assert_eq!(args.common.icon, ":computer:");
+        assert_eq!(args.common.retries, Some(0));
+        assert_eq!(args.channel.as_deref(), Some("#general"));
+        assert_eq!(args.message.as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn environment_overrides_profile() {
+        // SAFETY: no other test reads these variables, which stand in for the real ones. They are set first, as
+        // clap reads them when the command is built
+        unsafe {
+            env::set_var(
+                "ROCKET_NOTIFY_TEST_URL",
+                "https://env.example.com/hooks/x/y",
+            );
+            env::set_var("ROCKET_NOTIFY_TEST_ALIAS", "Env");
+        }
+        let command = command(&[
+            ("url", "ROCKET_NOTIFY_TEST_URL"),
+            ("alias", "ROCKET_NOTIFY_TEST_ALIAS"),
+        ]);
+        let args = configure(command, &[], true).unwrap();
+        assert_eq!(
+            url(&args),
+            ("https://env.example.com/hooks/x/y", "Option".into())
+        );
+        assert_eq!(args.common.alias, "Env");
+        assert_eq!(args.common.retries, Some(2));
+    }
+
+    #[test]
+    fn credential_before_profile() {
+        let args = configure(command(&[]), &[], true).unwrap();
+        let (found, source) = url(&args);
+        assert_eq!(found, CREDENTIAL);
+        assert!(source.starts_with("Credential("), "{source}");
+    }
+
+    #[test]
+    fn single_argument_is_message() {
+        let args = configure(command(&[]), &["hello"], false).unwrap();
+        assert_eq!(args.message.as_deref(), Some("hello"));
+        assert_eq!(args.common.channel(args.channel.as_ref()).unwrap(), "#ops");
+
+        // Payloads may say where they go, so the argument stays the channel
+        let args = configure(command(&[]), &["--payload", "message.json", "#qa"], false).unwrap();
+        assert_eq!(args.channel.as_deref(), Some("#qa"));
+        assert_eq!(args.message, None);
+
+        // Without a channel in the profile, it is the channel
+        let args = configure(command(&[]), &["--profile", "other", "#qa"], false).unwrap();
+        assert_eq!(args.channel.as_deref(), Some("#qa"));
+        assert_eq!(args.message, None);
+    }
+}
@@ -0,0 +1,196 @@
+use clap::{ArgAction, Args, ValueEnum};
+use rocketchat::client::ErrorKind;
+use serde_json::{Value, json};
+use std::{
+    env,
+    fmt::Display,
+    io::{self, IsTerminal},
+    sync::OnceLock,
+};
+use tracing::Level;
+
+use crate::deliver::{self, Outcome};
+
+static OUTPUT: OnceLock<OutputArgs> = OnceLock::new();
+
+/// How results and errors are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human readable messages
+    #[default]
+    Text,
+
+    /// A JSON object per line, with the server's response for sent messages
+    Json,
+}
+
+/// Options controlling what is printed
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub struct OutputArgs {
+    /// Format of results and errors
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = Format::Text)]
+    #[arg(env = "ROCKET_NOTIFY_OUTPUT")]
+    output: Format,
+
+    /// Only print errors
+    #[arg(short, long, env = "ROCKET_NOTIFY_QUIET")]
+    quiet: bool,
+
+    /// Log requests and responses to stderr. Repeat to include their bodies
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Print the JSON of each message instead of sending it
+    #[arg(long, env = "ROCKET_NOTIFY_DRY_RUN")]
+    dry_run: bool,
+}
+
+/// Use these options for everything printed after
+pub fn init(args: OutputArgs) {
+    let level = match args.verbose {
+        0 => None,
+        1 => Some(Level::DEBUG),
+        _ => Some(Level::TRACE),
+    };
+    if let Some(level) = level {
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(io::stderr)
+            .with_ansi(colored(io::stderr().is_terminal()))
+            .init();
+    }
+    let _ = OUTPUT.set(args);
+}
+
+/// Whether messages are printed instead of sent
+pub fn dry_run() -> bool {
+    options().dry_run
+}
+
+/// How results and errors are printed
+pub fn format() -> Format {
+    options().output
+}
+
+fn options() -> OutputArgs {
+    OUTPUT.get().copied().unwrap_or_default()
+}
+
+/// Whether to use colors, only when printing to a terminal, and `NO_COLOR` isn't set
+pub fn colored(terminal: bool) -> bool {
+    terminal && env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+}
+
+fn label(terminal: bool, style: &str, label: &str) -> String {
+    if colored(terminal) {
+        format!("\x1b[{style}m{label}:\x1b[m")
+    } else {
+        format!("{label}:")
+    }
+}
+
+/// A JSON error object, with a `kind` scripts can match on
+pub fn error_json(destination: Option<&str>, kind: &str, message: &dyn Display) -> Value {
+    json!({
+        "success": false,
+        "destination": destination,
+        "error": { "kind": kind, "message": message.to_string() },
+    })
+}
+
+// Convenience fns for pretty-printing errors
+pub fn print_err<S: Display>(msg: S) {
+    match options().output {
+        Format::Text => eprintln!(
+            "{} {msg}",
+            label(io::stderr().is_terminal(), "1;31", "error")
+        ),
+        Format::Json => eprintln!("{}", error_json(None, "error", &msg)),
+    }
+}
+pub fn print_warn<S: Display>(msg: S) {
+    match options().output {
+        Format::Text => eprintln!(
+            "{} {msg}",
+            label(io::stderr().is_terminal(), "1;33", "warning")
+        ),
+        Format::Json => eprintln!("{}", json!({ "warning": msg.to_string() })),
+    }
+}
+fn print_success<S: Display>(msg: S) {
+    println!(
+        "{} {msg}",
+        label(io::stdout().is_terminal(), "0;32", "success")
+    );
+}
+
+// Empty values are missing from minimal responses, which only say whether they succeeded
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value).filter(|value| !value.is_empty())
+}
+
+/// Report a message sent or queued for `destination`, or wherever its payload said
+pub fn sent(destination: Option<&str>, outcome: &Outcome) {
+    let options = options();
+    if options.quiet {
+        return;
+    }
+    match (options.output, outcome, destination) {
+        // The message was already printed
+        (_, Outcome::Printed, _) => {}
+        (Format::Text, Outcome::Sent(_), Some(destination)) => {
+            print_success(format!("message sent to {destination}"));
+        }
+        (Format::Text, Outcome::Sent(_), None) => print_success("message sent!"),
+        (Format::Text, Outcome::Queued(_), Some(destination)) => {
+            print_success(format!("message queued for {destination}"));
+        }
+        (Format::Text, Outcome::Queued(_), None) => print_success("message queued!"),
+        (Format::Json, outcome, destination) => {
+            println!("{}", outcome_json(destination, outcome));
+        }
+    }
+}
+
+/// A JSON object describing a message that didn't fail, with the server's response if it was sent
+pub fn outcome_json(destination: Option<&str>, outcome: &Outcome) -> Value {
+    match outcome {
+        Outcome::Sent(response) => json!({
+            "success": true,
+            "destination": destination,
+            "ts": Some(response.ts()).filter(|ts| ts.timestamp() != 0),
+            "channel": non_empty(response.channel()),
+            "message_id": non_empty(response.message().id()),
+            "room_id": non_empty(response.message().rid()),
+            "response": response,
+        }),
+        Outcome::Queued(id) => {
+            json!({ "success": true, "destination": destination, "queued": true, "queue_id": id })
+        }
+        Outcome::Printed => json!({ "success": true, "destination": destination }),
+    }
+}
+
+/// A short name for the kind of error
+pub fn kind(error: &deliver::Error) -> &'static str {
+    match error.kind() {
+        ErrorKind::InvalidUrl => "invalid_url",
+        ErrorKind::Network => "network",
+        ErrorKind::Timeout => "timeout",
+        ErrorKind::Rejected => "rejected",
+        ErrorKind::RateLimited => "rate_limited",
+    }
+}
+
+/// Report a message that couldn't be sent to `destination`, or wherever its payload said
+pub fn failed(destination: Option<&str>, error: &deliver::Error) {
+    match (options().output, destination) {
+        (Format::Text, Some(destination)) => {
+            print_err(format!("failed to send to {destination}: {error}"));
+        }
+        (Format::Text, None) => print_err(error),
+        (Format::Json, destination) => {
+            eprintln!("{}", error_json(destination, kind(error), error));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use clap::ValueEnum;
+use rocketchat::{blocks::Block, request::Message};
+use serde_json::value::RawValue;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PayloadFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl PayloadFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => PayloadFormat::Json,
+            Some("toml") => PayloadFormat::Toml,
+            _ => PayloadFormat::Yaml,
+        }
+    }
+}
+
+// Open a file for reading, where '-' refers to stdin
+pub fn open_input(path: &Path) -> Result<Box<dyn Read>, String> {
+    if path == Path::new("-") {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    File::open(path)
+        .map(|file| Box::new(BufReader::new(file)) as Box<dyn Read>)
+        .map_err(|e| format!("failed to open {}: {e}", path.display()))
+}
+
+pub fn read_to_string(path: &Path) -> Result<String, String> {
+    let mut content = String::new();
+    open_input(path)?
+        .read_to_string(&mut content)
+        .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    Ok(content)
+}
+
+pub fn read_blocks(path: &Path) -> Result<Vec<Block>, String> {
+    serde_json::from_reader(open_input(path)?)
+        .map_err(|e| format!("failed to parse blocks from {}: {e}", path.display()))
+}
+
+/// Read a JSON payload to post as it is, keeping its keys in order, and its formatting
+pub fn read_raw_payload(path: &Path) -> Result<Box<RawValue>, String> {
+    serde_json::from_str(&read_to_string(path)?)
+        .map_err(|e| format!("failed to parse payload from {}: {e}", path.display()))
+}
+
+pub fn read_payload(path: &Path, format: Option<PayloadFormat>) -> Result<Message, String> {
+    let content = read_to_string(path)?;
+    parse_payload(
+        &content,
+        format.unwrap_or_else(|| PayloadFormat::from_path(path)),
+    )
+    .map_err(|e| format!("failed to parse message from {}: {e}", path.display()))
+}
+
+pub fn parse_payload(content: &str, format: PayloadFormat) -> Result<Message, String> {
+    match format {
+        PayloadFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        PayloadFormat::Yaml => serde_yaml_ng::from_str(content).map_err(|e| e.to_string()),
+        PayloadFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+    }
+}
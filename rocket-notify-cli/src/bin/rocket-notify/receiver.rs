@@ -0,0 +1,328 @@
+use clap::Args;
+use rocketchat::request::{Destination, Message};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::PathBuf};
+use tiny_http::Request;
+
+use crate::{
+    CommonArgs,
+    alertmanager::{self, MAX_ALERTS, Notification, Payload},
+    auth::{self, Caller},
+    deliver::Transport,
+    exit, grafana,
+    http::{self, Failure, ListenArgs},
+    parse_key_value, print_err, secret,
+};
+
+// Path Alertmanager notifications are posted to
+const ALERTMANAGER_PATH: &str = "/v1/alertmanager";
+
+// Path Grafana notifications are posted to
+const GRAFANA_PATH: &str = "/v1/grafana";
+
+#[derive(Debug, Args)]
+pub struct ReceiverArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    #[command(flatten)]
+    listen: ListenArgs,
+
+    /// Bearer token Alertmanager and Grafana must send, accepted along with the API keys in the profile's `keys`
+    #[arg(long, env = "ROCKET_NOTIFY_TOKEN", hide_env_values = true)]
+    #[arg(conflicts_with = "token_file")]
+    token: Option<String>,
+
+    /// Read the bearer token from the first line of a file
+    #[arg(long, value_name = "FILE", env = "ROCKET_NOTIFY_TOKEN_FILE")]
+    token_file: Option<PathBuf>,
+
+    /// Send alerts for an Alertmanager receiver to a channel, like 'team-ops=#ops'. Can be repeated
+    #[arg(long, value_name = "RECEIVER=CHANNEL", value_parser = parse_key_value)]
+    route: Vec<(String, String)>,
+
+    /// Most alerts shown in a message, with the rest only counted
+    #[arg(long, value_name = "N", default_value_t = MAX_ALERTS)]
+    #[arg(env = "ROCKET_NOTIFY_MAX_ALERTS")]
+    max_alerts: usize,
+
+    /// Channel to send alerts to when their receiver has no route, like '#general' or '@eric'
+    #[arg(env = "ROCKET_NOTIFY_CHANNEL")]
+    channel: Option<String>,
+}
+
+#[derive(Debug)]
+struct Receiver<'a> {
+    args: &'a ReceiverArgs,
+    transport: Transport,
+    callers: Vec<Caller>,
+    // Channels by Alertmanager receiver
+    routes: BTreeMap<String, String>,
+    // Channel for receivers without a route
+    channel: Option<String>,
+}
+
+impl Receiver<'_> {
+    fn handle(&self, request: &mut Request) -> Result<(u16, Value), Failure> {
+        let path = http::post(request, &[ALERTMANAGER_PATH, GRAFANA_PATH])?;
+        let caller = http::authenticate(request, &self.callers)?;
+        match path {
+            GRAFANA_PATH => self.notify::<grafana::Alert>(request, caller),
+            _ => self.notify::<alertmanager::Alert>(request, caller),
+        }
+    }
+
+    fn notify<A>(&self, request: &mut Request, caller: &Caller) -> Result<(u16, Value), Failure>
+    where
+        A: Notification + DeserializeOwned,
+    {
+        let payload: Payload<A> = http::read_json(request, "invalid_alert")?;
+        let (message, channel) = self.route(caller, &payload)?;
+        http::send(&self.transport, &message, channel)
+    }
+
+    // Check the payload, and that the client may send to the channel of its receiver. Returns the message with the
+    // channel it is sent to
+    fn route<A: Notification>(
+        &self,
+        caller: &Caller,
+        payload: &Payload<A>,
+    ) -> Result<(Message, &String), Failure> {
+        payload
+            .validate()
+            .map_err(|e| Failure::new(400, "invalid_alert", e))?;
+
+        let channel = self
+            .routes
+            .get(&payload.receiver)
+            .or(self.channel.as_ref())
+            .ok_or_else(|| {
+                Failure::new(
+                    400,
+                    "no_route",
+                    format!("no channel for receiver {}", payload.receiver),
+                )
+            })?;
+        let destination = channel
+            .parse::<Destination>()
+            .map(|destination| auth::destination_name(&destination))
+            .unwrap_or_default();
+        if !caller.may_send_to(&destination) {
+            return Err(Failure::new(
+                403,
+                "forbidden",
+                format!("{} may not send to {destination}", caller.name),
+            ));
+        }
+        let message = self
+            .args
+            .common
+            .sender(payload.message(self.args.max_alerts))
+            .channel(channel);
+        Ok((message, channel))
+    }
+}
+
+// The clients allowed to send alerts: those in the profile's `keys`, and any with the token given
+fn load_callers(args: &ReceiverArgs) -> Result<Vec<Caller>, String> {
+    let mut callers = auth::load_callers(&args.common.settings.keys)?;
+    let token = match (&args.token, &args.token_file) {
+        (Some(token), _) => Some(token.trim().to_string()),
+        (None, Some(path)) => Some(secret::read_file(path, "token file")?),
+        (None, None) => None,
+    };
+    match token {
+        Some(token) if token.is_empty() => return Err("the token is empty".into()),
+        Some(token) => callers.push(Caller::unrestricted("token", token)),
+        None => {}
+    }
+    if callers.is_empty() {
+        return Err(
+            "no tokens configured. Pass --token or --token-file, or add clients to `keys` in the config file profile"
+                .into(),
+        );
+    }
+    Ok(callers)
+}
+
+/// Receive alert notifications from Alertmanager and Grafana over HTTP, and send them as messages until interrupted. Returns the exit code
+pub fn receiver(args: &ReceiverArgs) -> i32 {
+    let mut routes = args.common.settings.routes.clone();
+    routes.extend(args.route.iter().cloned());
+    // Routes may cover every receiver, so a channel isn't required
+    let channel = args.common.channel(args.channel.as_ref()).ok();
+    let setup = args.common.transport().and_then(|transport| {
+        for channel in routes.values().chain(&channel) {
+            http::single_channel(channel)?;
+        }
+        Ok((transport, load_callers(args)?))
+    });
+    let (transport, callers) = match setup {
+        Ok(setup) => setup,
+        Err(e) => {
+            print_err(e);
+            return exit::USAGE;
+        }
+    };
+    let receiver = Receiver {
+        args,
+        transport,
+        callers,
+        routes,
+        channel,
+    };
+    http::serve(&args.listen, |request| receiver.handle(request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cli, Command, config::ApiKey};
+    use clap::Parser;
+
+    // Alertmanager's notification for `receiver`
+    fn alertmanager(receiver: &str) -> Payload {
+        serde_json::from_value(serde_json::json!({
+            "version": "4",
+            "receiver": receiver,
+            "status": "firing",
+            "groupLabels": { "alertname": "HighCPU" },
+            "externalURL": "http://alertmanager:9093",
+            "alerts": [{ "status": "firing", "labels": { "alertname": "HighCPU", "severity": "warning" } }]
+        }))
+        .unwrap()
+    }
+
+    fn args(extra: &[&str]) -> ReceiverArgs {
+        let args = [
+            "rocket-notify",
+            "receiver",
+            "--alias",
+            "Alerts",
+            "--icon",
+            ":bell:",
+        ];
+        let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+        let Some(Command::Receiver(args)) = cli.command else {
+            panic!("expected the receiver command");
+        };
+        args
+    }
+
+    fn new_receiver<'a>(args: &'a ReceiverArgs, channel: Option<&str>) -> Receiver<'a> {
+        let keys = BTreeMap::from([(
+            "ops".to_string(),
+            ApiKey {
+                key: Some("ops-key".into()),
+                channels: vec!["#ops".into()],
+                ..Default::default()
+            },
+        )]);
+        let mut callers = auth::load_callers(&keys).unwrap();
+        callers.push(Caller::unrestricted("token", "token"));
+        Receiver {
+            args,
+            transport: Transport::DryRun,
+            callers,
+            routes: BTreeMap::from([
+                ("team-ops".into(), "#ops".into()),
+                ("team-db".into(), "@dba".into()),
+            ]),
+            channel: channel.map(String::from),
+        }
+    }
+
+    fn caller<'a>(receiver: &'a Receiver, key: &str) -> &'a Caller {
+        auth::authenticate(&receiver.callers, Some(&format!("Bearer {key}"))).unwrap()
+    }
+
+    // Where the notification for `name` is sent, or the status it is refused with
+    fn route(receiver: &Receiver, key: &str, name: &str) -> Result<String, u16> {
+        receiver
+            .route(caller(receiver, key), &alertmanager(name))
+            .map(|(_, channel)| channel.clone())
+            .map_err(|failure| failure.status)
+    }
+
+    #[test]
+    fn routes_by_receiver() {
+        let args = args(&[]);
+        let receiver = new_receiver(&args, Some("#alerts"));
+        assert_eq!(route(&receiver, "token", "team-ops").unwrap(), "#ops");
+        assert_eq!(route(&receiver, "token", "team-db").unwrap(), "@dba");
+        assert_eq!(route(&receiver, "token", "team-web").unwrap(), "#alerts");
+        assert_eq!(
+            route(&new_receiver(&args, None), "token", "team-web"),
+            Err(400)
+        );
+    }
+
+    #[test]
+    fn routes_checked_against_keys() {
+        let args = args(&[]);
+        let receiver = new_receiver(&args, Some("#alerts"));
+        assert_eq!(route(&receiver, "ops-key", "team-ops").unwrap(), "#ops");
+        assert_eq!(route(&receiver, "ops-key", "team-db"), Err(403));
+        assert_eq!(route(&receiver, "ops-key", "team-web"), Err(403));
+    }
+
+    #[test]
+    fn message_from_sender() {
+        let args = args(&["--max-alerts", "0"]);
+        let receiver = new_receiver(&args, None);
+        let (message, _) = receiver
+            .route(caller(&receiver, "token"), &alertmanager("team-ops"))
+            .unwrap();
+        let message = serde_json::to_value(message).unwrap();
+        assert_eq!(message["channel"], "#ops");
+        assert_eq!(message["alias"], "Alerts");
+        assert_eq!(message["emoji"], ":bell:");
+        assert_eq!(
+            message["text"],
+            ":fire: *[FIRING:1] HighCPU*\n_and 1 more not shown_"
+        );
+        assert_eq!(message["attachments"].as_array().map_or(0, Vec::len), 0);
+    }
+
+    #[test]
+    fn grafana_notifications() {
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/grafana.json");
+        let payload: Payload<grafana::Alert> =
+            serde_json::from_str(&std::fs::read_to_string(fixture).unwrap()).unwrap();
+        let args = args(&[]);
+        let receiver = new_receiver(&args, Some("#alerts"));
+        let (message, channel) = receiver
+            .route(caller(&receiver, "token"), &payload)
+            .unwrap();
+        assert_eq!(channel, "#alerts");
+
+        // Rendered as when sent with `--from-grafana`, from the receiver's sender
+        let message = serde_json::to_value(message).unwrap();
+        let sent = serde_json::to_value(grafana::read_message(fixture.as_ref()).unwrap()).unwrap();
+        assert_eq!(message["alias"], "Alerts");
+        assert_eq!(message["text"], ":fire: *[FIRING:1] High memory usage*");
+        assert_eq!(message["attachments"], sent["attachments"]);
+        assert_eq!(
+            message["attachments"][0]["image_url"],
+            "https://grafana.example.com/public/img/attachments/memory.png"
+        );
+    }
+
+    #[test]
+    fn unsupported_versions_refused() {
+        let args = args(&[]);
+        let receiver = new_receiver(&args, Some("#alerts"));
+        let payload: Payload<grafana::Alert> = serde_json::from_value(serde_json::json!({
+            "version": "4",
+            "receiver": "team-ops",
+            "status": "firing",
+            "alerts": []
+        }))
+        .unwrap();
+        let failure = receiver
+            .route(caller(&receiver, "token"), &payload)
+            .unwrap_err();
+        assert_eq!((failure.status, failure.kind), (400, "invalid_alert"));
+    }
+}
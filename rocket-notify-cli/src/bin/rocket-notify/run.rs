@@ -0,0 +1,380 @@
+use clap::Args;
+use rocketchat::{
+    color::Color,
+    request::{Attachment, AttachmentField, Message},
+};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Write},
+    num::NonZeroUsize,
+    process::{Command, ExitStatus, Stdio},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{CommonArgs, HOSTNAME, deliver, exit, print_err, text};
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Title of the message
+    #[arg(short, long, env = "ROCKET_NOTIFY_TITLE")]
+    title: Option<String>,
+
+    /// Only send a message if the command fails
+    #[arg(long, env = "ROCKET_NOTIFY_ONLY_ON_FAILURE")]
+    only_on_failure: bool,
+
+    /// Number of lines of the command's output to include in the message
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 20,
+        env = "ROCKET_NOTIFY_TAIL_LINES"
+    )]
+    tail_lines: usize,
+
+    /// Channel to which the mesage will be sent, like '#general' or '@eric'. Separate several with commas
+    #[arg(env = "ROCKET_NOTIFY_CHANNEL")]
+    channel: Option<String>,
+
+    /// Command to run, and its arguments
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    command: Vec<String>,
+}
+
+// Longest line kept in the tail, in bytes. The rest of a longer line, like a progress bar redrawn with '\r', is
+// passed through but not kept
+const MAX_LINE: usize = 4096;
+
+// The last lines of output from both stdout and stderr, as they were interleaved
+#[derive(Debug, Default)]
+struct Tail {
+    lines: VecDeque<String>,
+    max_lines: usize,
+}
+
+impl Tail {
+    fn push(&mut self, line: &[u8]) {
+        if self.max_lines == 0 {
+            return;
+        }
+        if self.lines.len() == self.max_lines {
+            self.lines.pop_front();
+        }
+        let line = String::from_utf8_lossy(line);
+        self.lines
+            .push_back(text::strip_ansi(line.trim_end_matches(['\r', '\n'])));
+    }
+}
+
+// Pass output from the child through to `out`, while keeping the tail
+fn tee<R: Read, W: Write>(mut input: R, mut out: W, tail: &Mutex<Tail>) {
+    let mut buf = [0u8; 8192];
+    let mut line = Vec::new();
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let _ = out.write_all(&buf[..n]).and_then(|()| out.flush());
+
+        for chunk in buf[..n].split_inclusive(|b| *b == b'\n') {
+            let kept = chunk.len().min(MAX_LINE - line.len());
+            line.extend_from_slice(&chunk[..kept]);
+            if chunk.ends_with(b"\n") {
+                tail.lock().unwrap().push(&line);
+                line.clear();
+            }
+        }
+    }
+    if !line.is_empty() {
+        tail.lock().unwrap().push(&line);
+    }
+}
+
+// Quote arguments for display, so the command line could be pasted into a shell
+fn command_line(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c))
+            {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{:.1}s", duration.as_secs_f64()),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m {}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+#[cfg(unix)]
+fn exit_code(status: ExitStatus) -> (i32, String) {
+    use std::os::unix::process::ExitStatusExt;
+    match (status.code(), status.signal()) {
+        (Some(code), _) => (code, format!("exit status {code}")),
+        (None, Some(signal)) => (128 + signal, format!("killed by signal {signal}")),
+        (None, None) => (1, "unknown exit status".into()),
+    }
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: ExitStatus) -> (i32, String) {
+    let code = status.code().unwrap_or(1);
+    (code, format!("exit status {code}"))
+}
+
+// Run the command, returning its exit code and a description of the outcome
+fn execute(args: &RunArgs, tail: &Arc<Mutex<Tail>>) -> (i32, String) {
+    let mut child = match Command::new(&args.command[0])
+        .args(&args.command[1..])
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let code = if e.kind() == ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+            return (code, format!("failed to start: {e}"));
+        }
+    };
+
+    // Interrupts from the terminal also reach the child, so outlive it to report the outcome. Terminate the child
+    // if rocket-notify itself is asked to terminate
+    let terminate = Arc::new(AtomicBool::new(false));
+    let _ = signal_hook::flag::register(SIGINT, Arc::new(AtomicBool::new(false)));
+    let _ = signal_hook::flag::register(SIGTERM, Arc::clone(&terminate));
+
+    let readers = [
+        child.stdout.take().map(|stdout| {
+            let tail = Arc::clone(tail);
+            thread::spawn(move || tee(stdout, io::stdout(), &tail))
+        }),
+        child.stderr.take().map(|stderr| {
+            let tail = Arc::clone(tail);
+            thread::spawn(move || tee(stderr, io::stderr(), &tail))
+        }),
+    ];
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if terminate.swap(false, Ordering::Relaxed) => {
+                let _ = child.kill();
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(e) => break Err(e),
+        }
+    };
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+
+    match status {
+        Ok(status) => exit_code(status),
+        Err(e) => (1, format!("failed to wait for command: {e}")),
+    }
+}
+
+/// Run a command and send a message with its outcome, returning the exit code of the command
+pub fn run(args: &RunArgs) -> i32 {
+    let transport = args.common.transport();
+    let (transport, channels) = match transport
+        .and_then(|transport| Ok((transport, args.common.channels(args.channel.as_ref())?)))
+    {
+        Ok(found) => found,
+        Err(e) => {
+            print_err(e);
+            return exit::USAGE;
+        }
+    };
+
+    let tail = Arc::new(Mutex::new(Tail {
+        max_lines: args.tail_lines,
+        ..Default::default()
+    }));
+    let command_line = command_line(&args.command);
+
+    let start = Instant::now();
+    let (code, outcome) = execute(args, &tail);
+    let duration = start.elapsed();
+
+    let succeeded = code == 0;
+    if succeeded && args.only_on_failure {
+        return code;
+    }
+
+    let output = tail
+        .lock()
+        .unwrap()
+        .lines
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut attachment = Attachment::new()
+        .title(args.title.clone().unwrap_or(command_line.clone()))
+        .color(if succeeded {
+            Color::Success
+        } else {
+            Color::Danger
+        })
+        .field(
+            AttachmentField::new()
+                .short(true)
+                .title("Status")
+                .value(&outcome),
+        )
+        .field(
+            AttachmentField::new()
+                .short(true)
+                .title("Duration")
+                .value(format_duration(duration)),
+        )
+        .field(
+            AttachmentField::new()
+                .short(true)
+                .title("Host")
+                .value(HOSTNAME.as_str()),
+        )
+        .field(
+            AttachmentField::new()
+                .title("Command")
+                .value(format!("`{command_line}`")),
+        );
+    if !output.is_empty() {
+        attachment = attachment.text(text::code_block(&output, ""));
+    }
+
+    let message = args.common.sender(
+        Message::new()
+            .text(if succeeded {
+                format!(
+                    ":white_check_mark: Command succeeded on {}",
+                    HOSTNAME.as_str()
+                )
+            } else {
+                format!(":x: Command failed on {}: {outcome}", HOSTNAME.as_str())
+            })
+            .attachment(attachment),
+    );
+
+    deliver::deliver(&transport, &message, &channels, NonZeroUsize::MIN);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tail(max_lines: usize) -> Tail {
+        Tail {
+            max_lines,
+            ..Tail::default()
+        }
+    }
+
+    // The output passed through, and the tail kept, reading `input` in chunks of `chunk` bytes
+    fn run_tee(input: &[u8], chunk: usize, max_lines: usize) -> (Vec<u8>, Vec<String>) {
+        struct Chunks<'a>(&'a [u8], usize);
+
+        impl Read for Chunks<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = self.0.len().min(self.1).min(buf.len());
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let tail = Mutex::new(tail(max_lines));
+        let mut out = Vec::new();
+        tee(Chunks(input, chunk), &mut out, &tail);
+        (out, tail.into_inner().unwrap().lines.into())
+    }
+
+    #[test]
+    fn tail_keeps_last_lines() {
+        let mut tail = tail(2);
+        for line in ["one\n", "two\r\n", "\x1b[31mthree\x1b[0m\n"] {
+            tail.push(line.as_bytes());
+        }
+        assert_eq!(tail.lines, ["two", "three"]);
+
+        let mut none = self::tail(0);
+        none.push(b"one\n");
+        assert!(none.lines.is_empty());
+    }
+
+    #[test]
+    fn tee_passes_output_through() {
+        let input = b"first\nsecond line\nno newline";
+        for chunk in [1, 4, 8192] {
+            let (out, lines) = run_tee(input, chunk, 10);
+            assert_eq!(out, input, "{chunk}");
+            assert_eq!(lines, ["first", "second line", "no newline"], "{chunk}");
+        }
+    }
+
+    #[test]
+    fn tee_caps_long_lines() {
+        let mut input = vec![b'#'; 3 * MAX_LINE];
+        input.extend_from_slice(b"\ndone\n");
+        for chunk in [1000, 8192] {
+            let (out, lines) = run_tee(&input, chunk, 10);
+            assert_eq!(out, input);
+            assert_eq!(lines, ["#".repeat(MAX_LINE), "done".into()]);
+        }
+    }
+
+    #[test]
+    fn command_line_quotes() {
+        let command =
+            |args: &[&str]| command_line(&args.iter().map(|arg| (*arg).into()).collect::<Vec<_>>());
+        assert_eq!(
+            command(&["make", "-j4", "CC=clang", "user@host:/srv/a_b.tar.gz"]),
+            "make -j4 CC=clang user@host:/srv/a_b.tar.gz"
+        );
+        assert_eq!(
+            command(&["echo", "hello world", "", "$HOME", "it's"]),
+            r"echo 'hello world' '' '$HOME' 'it'\''s'"
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_millis(250)), "0.2s");
+        assert_eq!(format_duration(Duration::from_millis(59_940)), "59.9s");
+        assert_eq!(format_duration(Duration::from_secs(61)), "1m 1s");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59m 59s");
+        assert_eq!(format_duration(Duration::from_hours(1)), "1h 0m 0s");
+        assert_eq!(format_duration(Duration::from_secs(90_061)), "25h 1m 1s");
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    env,
+    fmt::{self, Display},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::output::print_warn;
+
+/// Name of the systemd credential holding the webhook URL, as in `LoadCredential=rocket-notify-url:/etc/rocket-notify/url`
+pub const CREDENTIAL: &str = "rocket-notify-url";
+
+// The first line of a secret, without surrounding whitespace
+fn first_line(content: &str) -> Option<String> {
+    content
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+}
+
+/// Where the webhook URL was read from
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// The `--url` option, or `ROCKET_NOTIFY_URL`
+    Option,
+
+    /// The `url` of a config file profile
+    Profile,
+
+    /// A file
+    File(PathBuf),
+
+    /// The output of a shell command
+    Command(String),
+
+    /// A systemd credential
+    Credential(PathBuf),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Option => f.write_str("--url or ROCKET_NOTIFY_URL"),
+            Source::Profile => f.write_str("the config file profile"),
+            Source::File(path) => write!(f, "file {}", path.display()),
+            Source::Command(command) => write!(f, "command `{command}`"),
+            Source::Credential(path) => write!(f, "systemd credential {}", path.display()),
+        }
+    }
+}
+
+/// Describe the problem if users other than the owner can read the file
+#[cfg(unix)]
+pub fn permission_problem(path: &Path) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path).ok()?.permissions().mode();
+    (mode & 0o044 != 0).then(|| {
+        format!(
+            "{} is readable by other users (mode {:o}). Restrict it with `chmod 600 {}`",
+            path.display(),
+            mode & 0o777,
+            path.display()
+        )
+    })
+}
+
+/// Describe the problem if users other than the owner can read the file
+#[cfg(not(unix))]
+pub fn permission_problem(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Read a secret, like a webhook URL, from the first line of a file, warning if other users can read it. `what` names
+/// the file in errors, like 'URL file'
+pub fn read_file(path: &Path, what: &str) -> Result<String, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {what} {}: {e}", path.display()))?;
+    if let Some(problem) = permission_problem(path) {
+        print_warn(problem);
+    }
+    first_line(&content).ok_or(format!("{what} {} is empty", path.display()))
+}
+
+/// Read a webhook URL from the first line of a command's output, run with the shell, like `pass show rocket`
+pub fn run_command(command: &str) -> Result<String, String> {
+    let shell = if cfg!(windows) {
+        ["cmd", "/C"]
+    } else {
+        ["sh", "-c"]
+    };
+    let output = Command::new(shell[0])
+        .arg(shell[1])
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("failed to run URL command `{command}`: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("URL command `{command}` failed: {}", output.status));
+    }
+    first_line(&String::from_utf8_lossy(&output.stdout))
+        .ok_or(format!("URL command `{command}` printed nothing"))
+}
+
+/// Read the webhook URL passed to a systemd service as the `rocket-notify-url` credential, if there is one
+pub fn read_credential() -> Result<Option<(String, Source)>, String> {
+    let Some(dir) = env::var_os("CREDENTIALS_DIRECTORY") else {
+        return Ok(None);
+    };
+    let path = PathBuf::from(dir).join(CREDENTIAL);
+    // Credentials are only readable by the service, so their permissions aren't checked
+    match fs::read_to_string(&path) {
+        Ok(content) => match first_line(&content) {
+            Some(url) => Ok(Some((url, Source::Credential(path)))),
+            None => Err(format!("credential {} is empty", path.display())),
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("failed to read credential {}: {e}", path.display())),
+    }
+}
+
+/// The webhook URL from the first of `url`, `file`, or `command` given, and where it was read from. A `url` is
+/// from `given`
+pub fn url(
+    url: Option<&String>,
+    file: Option<&Path>,
+    command: Option<&str>,
+    given: Source,
+) -> Result<Option<(String, Source)>, String> {
+    Ok(if let Some(url) = url {
+        Some((url.clone(), given))
+    } else if let Some(file) = file {
+        Some((
+            read_file(file, "URL file")?,
+            Source::File(file.to_path_buf()),
+        ))
+    } else if let Some(command) = command {
+        Some((run_command(command)?, Source::Command(command.to_string())))
+    } else {
+        None
+    })
+}
@@ -0,0 +1,202 @@
+use minijinja::{AutoEscape, Environment, UndefinedBehavior, path_loader};
+use rocketchat::request::Message;
+use serde_json::{Map, Value};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    HOSTNAME,
+    payload::{self, PayloadFormat},
+};
+
+const EXTENSIONS: [&str; 3] = ["j2", "jinja", "jinja2"];
+
+/// Variables available to a template, in addition to `env` and `hostname`
+#[derive(Debug, Default)]
+pub struct Vars(Map<String, Value>);
+
+impl Vars {
+    /// Read variables from a JSON object
+    pub fn from_json(path: &Path) -> Result<Self, String> {
+        match serde_json::from_str(&payload::read_to_string(path)?) {
+            Ok(Value::Object(vars)) => Ok(Vars(vars)),
+            Ok(_) => Err(format!(
+                "variables in {} must be a JSON object",
+                path.display()
+            )),
+            Err(e) => Err(format!(
+                "failed to parse variables from {}: {e}",
+                path.display()
+            )),
+        }
+    }
+
+    /// Add variables, replacing any with the same name
+    pub fn extend(&mut self, vars: Vars) {
+        self.0.extend(vars.0);
+    }
+
+    pub fn insert<K: Into<String>, V: Into<Value>>(&mut self, key: K, value: V) {
+        self.0.insert(key.into(), value.into());
+    }
+}
+
+impl From<Map<String, Value>> for Vars {
+    fn from(vars: Map<String, Value>) -> Self {
+        Vars(vars)
+    }
+}
+
+// Find a template by path, or by name in the template directory
+fn resolve(template: &str, template_dir: Option<&Path>) -> Result<(PathBuf, String), String> {
+    let path = Path::new(template);
+    if path.is_file() {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        return Ok((dir, name.to_string()));
+    }
+
+    let Some(dir) = template_dir else {
+        return Err(format!("template {template} not found"));
+    };
+    // Like `deploy`, `deploy.j2`, or `deploy.yaml.j2`
+    ["", ".json", ".yaml", ".yml", ".toml"]
+        .iter()
+        .flat_map(|format| {
+            std::iter::once(String::new())
+                .chain(EXTENSIONS.iter().map(|ext| format!(".{ext}")))
+                .map(move |ext| format!("{template}{format}{ext}"))
+        })
+        .find(|name| dir.join(name).is_file())
+        .map(|name| (dir.to_path_buf(), name))
+        .ok_or(format!(
+            "template {template} not found in {}",
+            dir.display()
+        ))
+}
+
+// Payload format of the rendered template, like `deploy.yaml.j2`
+fn format_of(name: &str) -> PayloadFormat {
+    let path = Path::new(name);
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if EXTENSIONS.contains(&ext) => {
+            PayloadFormat::from_path(Path::new(path.file_stem().unwrap_or_default()))
+        }
+        _ => PayloadFormat::from_path(path),
+    }
+}
+
+/// Render a message payload template, given either as a path or as the name of a template in `template_dir`
+pub fn render(
+    template: &str,
+    template_dir: Option<&Path>,
+    vars: Vars,
+    format: Option<PayloadFormat>,
+) -> Result<Message, String> {
+    let (dir, name) = resolve(template, template_dir)?;
+    let format = format.unwrap_or_else(|| format_of(&name));
+
+    let mut environment = Environment::new();
+    environment.set_loader(path_loader(dir));
+    environment.set_undefined_behavior(UndefinedBehavior::Strict);
+    // Values are quoted as JSON in JSON and YAML payloads, which YAML accepts as strings, so they can't break the
+    // payload. This applies to templates they include too, whatever their names
+    let escape = match format {
+        PayloadFormat::Json | PayloadFormat::Yaml => AutoEscape::Json,
+        PayloadFormat::Toml => AutoEscape::None,
+    };
+    environment.set_auto_escape_callback(move |_| escape);
+
+    let mut context = Map::new();
+    context.insert(
+        "env".into(),
+        env::vars()
+            .map(|(k, v)| (k, v.into()))
+            .collect::<Map<_, _>>()
+            .into(),
+    );
+    context.insert("hostname".into(), HOSTNAME.clone().into());
+    context.extend(vars.0);
+
+    let rendered = environment
+        .get_template(&name)
+        .and_then(|template| template.render(&context))
+        .map_err(|e| format!("failed to render template {template}: {e:#}"))?;
+
+    payload::parse_payload(&rendered, format)
+        .map_err(|e| format!("failed to parse message rendered from template {template}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTILE: &str = "deploy: \"api\" failed\nchannel: '#other'\n- {see: logs}";
+
+    // The message rendered from a template `name` with `source`, given the hostile text as `text`
+    fn render_text(name: &str, source: &str) -> Value {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(name), source).unwrap();
+        let mut vars = Vars::default();
+        vars.insert("text", HOSTILE);
+        let message = render(name, Some(dir.path()), vars, None).unwrap();
+        serde_json::to_value(message).unwrap()
+    }
+
+    #[test]
+    fn formats_of_names() {
+        let format = |name| format!("{:?}", format_of(name));
+        assert_eq!(format("deploy.json"), "Json");
+        assert_eq!(format("deploy.json.j2"), "Json");
+        assert_eq!(format("deploy.toml.jinja"), "Toml");
+        assert_eq!(format("deploy.yml.jinja2"), "Yaml");
+        assert_eq!(format("deploy.j2"), "Yaml");
+        assert_eq!(format("deploy"), "Yaml");
+    }
+
+    #[test]
+    fn values_are_quoted_in_json() {
+        let message = render_text(
+            "deploy.json.j2",
+            r##"{"channel": "#ops", "text": {{ text }}}"##,
+        );
+        assert_eq!(message["channel"], "#ops");
+        assert_eq!(message["text"], HOSTILE);
+    }
+
+    #[test]
+    fn values_are_quoted_in_yaml() {
+        for name in [
+            "deploy.yaml.j2",
+            "deploy.yml.jinja",
+            "deploy.j2",
+            "deploy.jinja",
+            "deploy.jinja2",
+        ] {
+            let message = render_text(name, "channel: '#ops'\ntext: {{ text }}\n");
+            assert_eq!(message["channel"], "#ops", "{name}");
+            assert_eq!(message["text"], HOSTILE, "{name}");
+        }
+    }
+
+    #[test]
+    fn values_are_concatenated_before_quoting() {
+        let message = render_text(
+            "deploy.yaml.j2",
+            "channel: '#ops'\ntext: {{ 'Deploy: ' ~ text }}\n",
+        );
+        assert_eq!(message["text"], format!("Deploy: {HOSTILE}"));
+    }
+
+    #[test]
+    fn values_are_not_quoted_in_toml() {
+        let message = render_text(
+            "deploy.toml.j2",
+            "channel = \"#ops\"\ntext = {{ text | tojson }}\n",
+        );
+        assert_eq!(message["channel"], "#ops");
+        assert_eq!(message["text"], HOSTILE);
+    }
+}
@@ -0,0 +1,239 @@
+use clap::Args;
+
+// Remove ANSI escape sequences, like colors and cursor movement, from terminal output
+pub fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI, like `ESC[1;31m`, terminated by a byte in '@'..='~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC, like `ESC]0;title BEL`, terminated by BEL or `ESC\`
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // Two-character sequences, like `ESC(B`
+            Some('(' | ')' | '#') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+// Keep at most the last `lines` lines of text
+pub fn tail_lines(text: &str, lines: usize) -> &str {
+    if lines == 0 {
+        return "";
+    }
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    match text.rmatch_indices('\n').nth(lines - 1) {
+        Some((i, _)) => &text[i + 1..],
+        None => text,
+    }
+}
+
+// Keep at most the last `bytes` bytes of text, without splitting a character
+pub fn tail_bytes(text: &str, bytes: usize) -> &str {
+    let mut start = text.len().saturating_sub(bytes);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+// Wrap text in a markdown code block, with an optional language for syntax highlighting
+pub fn code_block(text: &str, language: &str) -> String {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    format!("```{language}\n{text}\n```")
+}
+
+/// Processing applied to message text
+#[derive(Debug, Default, Clone, Args)]
+pub struct Format {
+    /// Remove ANSI escape sequences, like terminal colors, from the message
+    #[arg(long, env = "ROCKET_NOTIFY_STRIP_ANSI")]
+    pub strip_ansi: bool,
+
+    /// Only send the last N lines of the message
+    #[arg(long, value_name = "N", env = "ROCKET_NOTIFY_TAIL_LINES")]
+    pub tail_lines: Option<usize>,
+
+    /// Only send the last N bytes of the message
+    #[arg(long, value_name = "N", env = "ROCKET_NOTIFY_TAIL_BYTES")]
+    pub tail_bytes: Option<usize>,
+
+    /// Wrap the message in a code block
+    #[arg(long, env = "ROCKET_NOTIFY_CODE")]
+    pub code: bool,
+
+    /// Language used to highlight the message code block
+    #[arg(long, value_name = "LANG", env = "ROCKET_NOTIFY_CODE_LANGUAGE")]
+    pub code_language: Option<String>,
+}
+
+impl Format {
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = if self.strip_ansi {
+            strip_ansi(text)
+        } else {
+            text.to_string()
+        };
+
+        let mut truncated = false;
+        if let Some(lines) = self.tail_lines {
+            let tail = tail_lines(&text, lines);
+            truncated |= tail.len() < text.strip_suffix('\n').unwrap_or(&text).len();
+            text = tail.to_string();
+        }
+        if let Some(bytes) = self.tail_bytes {
+            let tail = tail_bytes(&text, bytes);
+            truncated |= tail.len() < text.len();
+            text = tail.to_string();
+        }
+
+        if self.code || self.code_language.is_some() {
+            text = code_block(&text, self.code_language.as_deref().unwrap_or_default());
+        }
+        if truncated {
+            text.insert_str(0, "…\n");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_lines_keeps_last() {
+        assert_eq!(tail_lines("a\nb\nc", 2), "b\nc");
+        assert_eq!(tail_lines("a\nb\nc", 1), "c");
+        assert_eq!(tail_lines("a\nb\nc", 3), "a\nb\nc");
+        assert_eq!(tail_lines("a\nb\nc", 10), "a\nb\nc");
+        assert_eq!(tail_lines("", 1), "");
+    }
+
+    #[test]
+    fn tail_lines_trailing_newline() {
+        // A trailing newline ends the last line, rather than starting an empty one
+        assert_eq!(tail_lines("a\nb\nc\n", 2), "b\nc");
+        assert_eq!(tail_lines("a\n", 1), "a");
+        assert_eq!(tail_lines("a\n\n", 1), "");
+    }
+
+    #[test]
+    fn tail_lines_zero() {
+        assert_eq!(tail_lines("a\nb", 0), "");
+        assert_eq!(tail_lines("a", 0), "");
+        assert_eq!(tail_lines("", 0), "");
+    }
+
+    #[test]
+    fn tail_bytes_keeps_last() {
+        assert_eq!(tail_bytes("abcdef", 3), "def");
+        assert_eq!(tail_bytes("abc", 10), "abc");
+        assert_eq!(tail_bytes("abc", 0), "");
+    }
+
+    #[test]
+    fn tail_bytes_character_boundary() {
+        // 'é' is two bytes, and '🚀' four, so cutting inside them drops the whole character
+        assert_eq!(tail_bytes("héllo", 5), "éllo");
+        assert_eq!(tail_bytes("héllo", 4), "llo");
+        assert_eq!(tail_bytes("a🚀", 4), "🚀");
+        assert_eq!(tail_bytes("a🚀", 3), "");
+    }
+
+    #[test]
+    fn strip_ansi_csi() {
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m plain"), "red plain");
+        assert_eq!(strip_ansi("\x1b[2K\x1b[1Gdone"), "done");
+        assert_eq!(strip_ansi("é\x1b[32mü\x1b[m"), "éü");
+        // Unterminated at the end of the text
+        assert_eq!(strip_ansi("text\x1b[31"), "text");
+    }
+
+    #[test]
+    fn strip_ansi_osc() {
+        assert_eq!(strip_ansi("\x1b]0;title\x07text"), "text");
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        // An escape inside the sequence doesn't end it unless followed by a backslash
+        assert_eq!(strip_ansi("\x1b]0;a\x1bb\x07c"), "c");
+    }
+
+    #[test]
+    fn strip_ansi_other() {
+        assert_eq!(strip_ansi("\x1b(Bx\x1b)0y"), "xy");
+        assert_eq!(strip_ansi("\x1b=keypad"), "keypad");
+        assert_eq!(strip_ansi("no escapes, ünïcode"), "no escapes, ünïcode");
+    }
+
+    #[test]
+    fn apply_nothing() {
+        assert_eq!(Format::default().apply("a\nb\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn apply_marks_truncation() {
+        let format = Format {
+            tail_lines: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(format.apply("a\nb\nc\n"), "…\nb\nc");
+        // Nothing removed but the trailing newline
+        assert_eq!(format.apply("b\nc\n"), "b\nc");
+
+        let format = Format {
+            tail_bytes: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(format.apply("abcdef"), "…\ndef");
+        assert_eq!(format.apply("def"), "def");
+    }
+
+    #[test]
+    fn apply_strips_before_truncating() {
+        let format = Format {
+            strip_ansi: true,
+            tail_bytes: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(format.apply("\x1b[31mhello\x1b[0m"), "hello");
+    }
+
+    #[test]
+    fn apply_code_block() {
+        let format = Format {
+            tail_lines: Some(1),
+            code_language: Some("sh".into()),
+            ..Default::default()
+        };
+        assert_eq!(format.apply("a\nb\n"), "…\n```sh\nb\n```");
+
+        let format = Format {
+            code: true,
+            ..Default::default()
+        };
+        assert_eq!(format.apply("a\n"), "```\na\n```");
+    }
+}
@@ -0,0 +1,662 @@
+use clap::Args;
+use notify::{RecursiveMode, Watcher};
+use regex::Regex;
+use rocketchat::{
+    color::Color,
+    request::{Attachment, Message},
+};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufRead, BufReader, ErrorKind, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    CommonArgs, HOSTNAME,
+    deliver::{self, Transport},
+    exit, output, print_err, text,
+};
+
+// How often to check the file when no change notifications arrive, like on network filesystems
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long to wait for the lines following a match before sending it without them
+const CONTEXT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Most lines sent in a single message, so a run of matching lines is split across messages
+const MAX_HIT_LINES: usize = 50;
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Regular expression matching the lines to send
+    #[arg(
+        short,
+        long = "match",
+        value_name = "REGEX",
+        env = "ROCKET_NOTIFY_MATCH"
+    )]
+    pattern: Regex,
+
+    /// Number of lines to include before and after each matching line
+    #[arg(
+        short = 'C',
+        long,
+        value_name = "N",
+        default_value_t = 2,
+        env = "ROCKET_NOTIFY_CONTEXT"
+    )]
+    context: usize,
+
+    /// Title of the message. Defaults to the path of the file
+    #[arg(short, long, env = "ROCKET_NOTIFY_TITLE")]
+    title: Option<String>,
+
+    /// Color of the message header, either a level like 'success', 'info', 'warning', 'danger', or any CSS color
+    #[arg(short, long, default_value = "danger", env = "ROCKET_NOTIFY_COLOR")]
+    #[arg(value_parser = Color::from_str)]
+    color: Color,
+
+    /// Most messages to send per period. Further matches are sent as a single summary at the end of the period
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 5,
+        env = "ROCKET_NOTIFY_RATE_LIMIT"
+    )]
+    rate_limit: usize,
+
+    /// Period over which messages are rate limited, like '30s' or '5m'
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "1m",
+        env = "ROCKET_NOTIFY_RATE_PERIOD"
+    )]
+    #[arg(value_parser = humantime::parse_duration)]
+    rate_period: Duration,
+
+    /// Read the file from the beginning, instead of only lines written after starting
+    #[arg(long)]
+    from_start: bool,
+
+    /// Log file to watch
+    #[arg(value_name = "FILE")]
+    file: PathBuf,
+
+    /// Channel to which the mesage will be sent, like '#general' or '@eric'. Separate several with commas
+    #[arg(env = "ROCKET_NOTIFY_CHANNEL")]
+    channel: Option<String>,
+}
+
+// Identity of the file at a path, to notice when it is replaced by rotation
+#[cfg(unix)]
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+// Without a stable file identity, rotation is only noticed when the file shrinks
+#[cfg(not(unix))]
+type FileId = ();
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> FileId {}
+
+// Reads lines appended to a file, reopening it if it is rotated or truncated
+struct Follower {
+    path: PathBuf,
+    file: Option<BufReader<File>>,
+    id: Option<FileId>,
+    pos: u64,
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    fn new(path: &Path, from_start: bool) -> Self {
+        let mut follower = Follower {
+            path: path.to_path_buf(),
+            file: None,
+            id: None,
+            pos: 0,
+            partial: Vec::new(),
+        };
+        follower.open(from_start);
+        follower
+    }
+
+    fn open(&mut self, from_start: bool) {
+        let Ok(file) = File::open(&self.path) else {
+            return;
+        };
+        let mut file = BufReader::new(file);
+        self.pos = if from_start {
+            0
+        } else {
+            file.seek(SeekFrom::End(0)).unwrap_or(0)
+        };
+        self.id = file.get_ref().metadata().ok().as_ref().map(file_id);
+        self.file = Some(file);
+        self.partial.clear();
+    }
+
+    fn read_lines(&mut self, lines: &mut Vec<String>) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        loop {
+            match file.read_until(b'\n', &mut self.partial) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.pos += n as u64;
+                    if self.partial.ends_with(b"\n") {
+                        let line = String::from_utf8_lossy(&self.partial);
+                        lines.push(text::strip_ansi(line.trim_end_matches(['\r', '\n'])));
+                        self.partial.clear();
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    }
+
+    // Read any new complete lines, following the path to a new file after rotation
+    fn poll(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.file.is_none() {
+            // The file did not exist yet, so everything in it is new
+            self.open(true);
+        }
+        self.read_lines(&mut lines);
+
+        match fs::metadata(&self.path) {
+            // Replaced, so the remainder of the old file has been read and the new one starts from the beginning
+            Ok(metadata) if self.id.is_some_and(|id| id != file_id(&metadata)) => {
+                self.open(true);
+                self.read_lines(&mut lines);
+            }
+            // Truncated in place, like by `copytruncate`
+            Ok(metadata) if metadata.len() < self.pos => {
+                if let Some(file) = &mut self.file {
+                    self.pos = file.seek(SeekFrom::Start(0)).unwrap_or(0);
+                    self.partial.clear();
+                }
+                self.read_lines(&mut lines);
+            }
+            _ => {}
+        }
+        lines
+    }
+}
+
+// Matching lines along with their surrounding context
+#[derive(Debug, Default)]
+struct Hit {
+    lines: Vec<String>,
+    matches: usize,
+}
+
+struct Matcher {
+    pattern: Regex,
+    context: usize,
+    before: VecDeque<String>,
+    pending: Option<Hit>,
+    after: usize,
+    updated: Instant,
+}
+
+impl Matcher {
+    fn new(pattern: Regex, context: usize) -> Self {
+        Matcher {
+            pattern,
+            context,
+            before: VecDeque::new(),
+            pending: None,
+            after: 0,
+            updated: Instant::now(),
+        }
+    }
+
+    // Add a line, returning a hit once the lines following it have been collected
+    fn push(&mut self, line: String) -> Option<Hit> {
+        let matched = self.pattern.is_match(&line);
+        match &mut self.pending {
+            Some(hit) => {
+                hit.lines.push(line);
+                if matched {
+                    hit.matches += 1;
+                    self.after = self.context;
+                } else {
+                    self.after -= 1;
+                }
+            }
+            None if matched => {
+                let mut lines: Vec<_> = self.before.drain(..).collect();
+                lines.push(line);
+                self.pending = Some(Hit { lines, matches: 1 });
+                self.after = self.context;
+            }
+            None => {
+                if self.context > 0 {
+                    if self.before.len() == self.context {
+                        self.before.pop_front();
+                    }
+                    self.before.push_back(line);
+                }
+                return None;
+            }
+        }
+        self.updated = Instant::now();
+
+        let full = self
+            .pending
+            .as_ref()
+            .is_some_and(|hit| hit.lines.len() >= MAX_HIT_LINES);
+        if self.after == 0 || full {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
+
+    // A pending hit, if no following lines have been written for a while
+    fn expire(&mut self) -> Option<Hit> {
+        if self.updated.elapsed() >= CONTEXT_TIMEOUT {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
+}
+
+// Allows `limit` messages per period, counting the hits suppressed beyond that
+struct RateLimit {
+    limit: usize,
+    period: Duration,
+    start: Instant,
+    sent: usize,
+    suppressed: usize,
+    last: Option<Hit>,
+}
+
+impl RateLimit {
+    fn new(limit: usize, period: Duration) -> Self {
+        RateLimit {
+            limit,
+            period,
+            start: Instant::now(),
+            sent: 0,
+            suppressed: 0,
+            last: None,
+        }
+    }
+
+    fn allow(&mut self, hit: Hit) -> Option<Hit> {
+        if self.sent < self.limit {
+            self.sent += 1;
+            Some(hit)
+        } else {
+            self.suppressed += hit.matches;
+            self.last = Some(hit);
+            None
+        }
+    }
+
+    // At the end of a period, the number of matches suppressed during it and the last suppressed hit
+    fn end_period(&mut self, force: bool) -> Option<(usize, Hit)> {
+        if !force && self.start.elapsed() < self.period {
+            return None;
+        }
+        self.start = Instant::now();
+        self.sent = 0;
+        let suppressed = std::mem::take(&mut self.suppressed);
+        self.last.take().map(|hit| (suppressed, hit))
+    }
+}
+
+struct Notifier<'a> {
+    args: &'a WatchArgs,
+    transport: Transport,
+    channels: Vec<String>,
+}
+
+impl Notifier<'_> {
+    fn send(&self, text: String, hit: &Hit) {
+        let args = self.args;
+        let attachment = Attachment::new()
+            .title(
+                args.title
+                    .clone()
+                    .unwrap_or(args.file.display().to_string()),
+            )
+            .text(text::code_block(&hit.lines.join("\n"), ""))
+            .color(args.color.clone());
+        let message = args
+            .common
+            .sender(Message::new().text(text).attachment(attachment));
+        for channel in &self.channels {
+            if let Err(e) = deliver::send(&self.transport, &message.clone().channel(channel)) {
+                output::failed(Some(channel), &e);
+            }
+        }
+    }
+
+    fn hit(&self, hit: &Hit) {
+        let lines = if hit.matches == 1 { "line" } else { "lines" };
+        self.send(
+            format!(
+                ":warning: {} matching {lines} in {} on {}",
+                hit.matches,
+                self.args.file.display(),
+                HOSTNAME.as_str()
+            ),
+            hit,
+        );
+    }
+
+    fn summary(&self, suppressed: usize, last: &Hit) {
+        let lines = if suppressed == 1 { "line" } else { "lines" };
+        self.send(
+            format!(
+                ":rotating_light: {suppressed} more matching {lines} in {} on {} in the last {}, most recently:",
+                self.args.file.display(),
+                HOSTNAME.as_str(),
+                humantime::format_duration(self.args.rate_period)
+            ),
+            last,
+        );
+    }
+}
+
+/// Follow a file, sending lines matching a pattern until interrupted. Returns the exit code
+pub fn watch(args: &WatchArgs) -> i32 {
+    let transport = args.common.transport();
+    let (transport, channels) = match transport
+        .and_then(|transport| Ok((transport, args.common.channels(args.channel.as_ref())?)))
+    {
+        Ok(found) => found,
+        Err(e) => {
+            print_err(e);
+            return exit::USAGE;
+        }
+    };
+
+    // Watch the directory rather than the file, to notice the file being replaced
+    let dir = match args.file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            print_err(format!("failed to watch {}: {e}", args.file.display()));
+            return exit::FAILURE;
+        }
+    };
+    if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+        print_err(format!("failed to watch {}: {e}", dir.display()));
+        return exit::FAILURE;
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let _ = signal_hook::flag::register(SIGINT, Arc::clone(&stop));
+    let _ = signal_hook::flag::register(SIGTERM, Arc::clone(&stop));
+
+    let notifier = Notifier {
+        args,
+        transport,
+        channels,
+    };
+    let mut follower = Follower::new(&args.file, args.from_start);
+    let mut matcher = Matcher::new(args.pattern.clone(), args.context);
+    let mut rate_limit = RateLimit::new(args.rate_limit, args.rate_period);
+
+    loop {
+        let stopping = stop.load(Ordering::Relaxed);
+        if let Some((suppressed, last)) = rate_limit.end_period(false) {
+            notifier.summary(suppressed, &last);
+        }
+
+        let mut hits: Vec<_> = follower
+            .poll()
+            .into_iter()
+            .filter_map(|line| matcher.push(line))
+            .collect();
+        hits.extend(if stopping {
+            matcher.pending.take()
+        } else {
+            matcher.expire()
+        });
+        for hit in hits {
+            if let Some(hit) = rate_limit.allow(hit) {
+                notifier.hit(&hit);
+            }
+        }
+
+        if stopping {
+            if let Some((suppressed, last)) = rate_limit.end_period(true) {
+                notifier.summary(suppressed, &last);
+            }
+            return 0;
+        }
+
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(_) | Err(RecvTimeoutError::Timeout) => while rx.try_recv().is_ok() {},
+            Err(RecvTimeoutError::Disconnected) => {
+                print_err(format!("stopped watching {}", args.file.display()));
+                return exit::FAILURE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn matcher(context: usize) -> Matcher {
+        Matcher::new(Regex::new("ERROR").unwrap(), context)
+    }
+
+    // Push lines, returning the lines of each hit
+    fn push_all(matcher: &mut Matcher, lines: &[&str]) -> Vec<Vec<String>> {
+        lines
+            .iter()
+            .filter_map(|line| matcher.push((*line).to_string()))
+            .map(|hit| hit.lines)
+            .collect()
+    }
+
+    fn hit(matches: usize) -> Hit {
+        Hit {
+            lines: vec!["ERROR".into()],
+            matches,
+        }
+    }
+
+    // An instant `duration` ago, for state that expires
+    fn ago(duration: Duration) -> Instant {
+        Instant::now().checked_sub(duration).unwrap()
+    }
+
+    #[test]
+    fn matches_without_context() {
+        let mut matcher = matcher(0);
+        assert_eq!(
+            push_all(&mut matcher, &["a", "ERROR 1", "b", "ERROR 2"]),
+            [["ERROR 1"], ["ERROR 2"]]
+        );
+        assert!(matcher.pending.is_none());
+    }
+
+    #[test]
+    fn context_window() {
+        let mut matcher = matcher(2);
+        assert_eq!(
+            push_all(&mut matcher, &["a", "b", "c", "ERROR", "d", "e", "f"]),
+            [["b", "c", "ERROR", "d", "e"]]
+        );
+        // The line after a hit isn't context for the next
+        assert_eq!(
+            push_all(&mut matcher, &["ERROR", "g"]),
+            Vec::<Vec<String>>::new()
+        );
+        assert_eq!(matcher.pending.as_ref().unwrap().lines, ["f", "ERROR", "g"]);
+    }
+
+    #[test]
+    fn matches_extend_context() {
+        let mut matcher = matcher(2);
+        let hits: Vec<Hit> = ["a", "ERROR 1", "b", "ERROR 2", "c", "d"]
+            .into_iter()
+            .filter_map(|line| matcher.push(line.to_string()))
+            .collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].lines, ["a", "ERROR 1", "b", "ERROR 2", "c", "d"]);
+        assert_eq!(hits[0].matches, 2);
+    }
+
+    #[test]
+    fn long_runs_split() {
+        let mut matcher = matcher(1);
+        let lines = vec!["ERROR"; MAX_HIT_LINES * 2 + 1];
+        let hits = push_all(&mut matcher, &lines);
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|lines| lines.len() == MAX_HIT_LINES));
+        assert_eq!(matcher.pending.as_ref().unwrap().lines.len(), 1);
+    }
+
+    #[test]
+    fn pending_hits_expire() {
+        let mut matcher = matcher(3);
+        assert!(matcher.expire().is_none());
+        assert!(matcher.push("ERROR".into()).is_none());
+        assert!(matcher.expire().is_none());
+
+        matcher.updated = ago(CONTEXT_TIMEOUT);
+        let hit = matcher.expire().unwrap();
+        assert_eq!(hit.lines, ["ERROR"]);
+        assert!(matcher.expire().is_none());
+    }
+
+    #[test]
+    fn rate_limit_suppresses() {
+        let mut rate_limit = RateLimit::new(2, Duration::from_mins(1));
+        assert!(rate_limit.allow(hit(1)).is_some());
+        assert!(rate_limit.allow(hit(1)).is_some());
+        assert!(rate_limit.allow(hit(2)).is_none());
+        assert!(rate_limit.allow(hit(3)).is_none());
+        assert!(rate_limit.end_period(false).is_none());
+
+        let (suppressed, last) = rate_limit.end_period(true).unwrap();
+        assert_eq!(suppressed, 5);
+        assert_eq!(last.matches, 3);
+        assert!(rate_limit.allow(hit(1)).is_some());
+    }
+
+    #[test]
+    fn rate_limit_period_summary() {
+        let mut rate_limit = RateLimit::new(1, Duration::from_mins(1));
+        assert!(rate_limit.allow(hit(1)).is_some());
+        rate_limit.start = ago(Duration::from_mins(1));
+        // Nothing was suppressed, so there is no summary, but the period starts over
+        assert!(rate_limit.end_period(false).is_none());
+        assert!(rate_limit.allow(hit(1)).is_some());
+        assert!(rate_limit.allow(hit(4)).is_none());
+
+        rate_limit.start = ago(Duration::from_mins(1));
+        let (suppressed, last) = rate_limit.end_period(false).unwrap();
+        assert_eq!((suppressed, last.matches), (4, 4));
+        assert!(rate_limit.end_period(true).is_none());
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn follows_appended_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "old\n");
+
+        let mut follower = Follower::new(&path, false);
+        assert!(follower.poll().is_empty());
+        append(&path, "one\ntw");
+        assert_eq!(follower.poll(), ["one"]);
+        append(&path, "o\r\n\x1b[31mthree\x1b[0m\n");
+        assert_eq!(follower.poll(), ["two", "three"]);
+
+        let mut follower = Follower::new(&path, true);
+        assert_eq!(follower.poll(), ["old", "one", "two", "three"]);
+    }
+
+    #[test]
+    fn follows_created_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut follower = Follower::new(&path, false);
+        assert!(follower.poll().is_empty());
+        append(&path, "first\n");
+        assert_eq!(follower.poll(), ["first"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_renamed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "before\n");
+        let mut follower = Follower::new(&path, false);
+
+        // Lines written to the old file before it was replaced are still read
+        append(&path, "one\n");
+        fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        append(&dir.path().join("app.log.1"), "two\n");
+        append(&path, "three\n");
+        assert_eq!(follower.poll(), ["one", "two", "three"]);
+
+        append(&path, "four\n");
+        assert_eq!(follower.poll(), ["four"]);
+    }
+
+    #[test]
+    fn follows_truncated_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "a long line before rotation\n");
+        let mut follower = Follower::new(&path, false);
+        append(&path, "one\n");
+        assert_eq!(follower.poll(), ["one"]);
+
+        // Like logrotate's `copytruncate`
+        fs::copy(&path, dir.path().join("app.log.1")).unwrap();
+        File::create(&path).unwrap();
+        append(&path, "two\n");
+        assert_eq!(follower.poll(), ["two"]);
+        append(&path, "three\n");
+        assert_eq!(follower.poll(), ["three"]);
+    }
+}
@@ -0,0 +1,604 @@
+//! Rocket.Chat [UIKit](https://developer.rocket.chat/docs/uikit) layout blocks.
+//!
+//! Blocks allow for richer message layouts than [`Attachment`](crate::request::Attachment)s, and are
+//! added to a message with [`Message::block`](crate::request::Message::block).
+//!
+//! ### Example:
+//! ```
+//! use rocketchat::{
+//!     blocks::{Actions, Block, Button, ButtonStyle, Context, Section, Text},
+//!     request::Message,
+//! };
+//!
+//! let message = Message::new()
+//!     .channel("#deploys")
+//!     .block(Section::new().text(Text::markdown("*Deploy finished* on `prod`")))
+//!     .block(Block::Divider)
+//!     .block(Context::new().element(Text::plain("took 42s")))
+//!     .block(
+//!         Actions::new().element(
+//!             Button::new()
+//!                 .action_id("open-logs")
+//!                 .text("Open logs")
+//!                 .url("https://ci.example.com/jobs/42")
+//!                 .style(ButtonStyle::Primary),
+//!         ),
+//!     );
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// A text object, rendered either as plain text or as markdown.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    /// Text rendered verbatim
+    PlainText {
+        text: String,
+
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        emoji: bool,
+    },
+
+    /// Text rendered as markdown
+    Mrkdwn { text: String },
+}
+
+impl Text {
+    /// Create a new plain [`Text`].
+    #[must_use]
+    pub fn plain<S: Into<String>>(text: S) -> Self {
+        Text::PlainText {
+            text: text.into(),
+            emoji: false,
+        }
+    }
+
+    /// Create a new markdown [`Text`].
+    #[must_use]
+    pub fn markdown<S: Into<String>>(text: S) -> Self {
+        Text::Mrkdwn { text: text.into() }
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        Text::plain(text)
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        Text::plain(text)
+    }
+}
+
+/// The visual style of a [`Button`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonStyle {
+    Primary,
+    Secondary,
+    Danger,
+}
+
+/// An interactive button element, which either opens a url or triggers an action.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Button {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    action_id: String,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    block_id: String,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    app_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<Text>,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    url: String,
+
+    #[serde(skip_serializing_if = "String::is_empty")]
+    value: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    style: Option<ButtonStyle>,
+}
+
+impl Button {
+    /// Create a new [`Button`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Set the identifier of the action triggered by the button.
+    #[must_use]
+    pub fn action_id<S: Into<String>>(mut self, action_id: S) -> Self {
+        self.action_id = action_id.into();
+        self
+    }
+
+    /// Set the identifier of the block containing the button.
+    #[must_use]
+    pub fn block_id<S: Into<String>>(mut self, block_id: S) -> Self {
+        self.block_id = block_id.into();
+        self
+    }
+
+    /// Set the identifier of the app handling the button action.
+    #[must_use]
+    pub fn app_id<S: Into<String>>(mut self, app_id: S) -> Self {
+        self.app_id = app_id.into();
+        self
+    }
+
+    /// Set the label of the button. Button labels are always plain text.
+    #[must_use]
+    pub fn text<S: Into<String>>(mut self, text: S) -> Self {
+        self.text = Some(Text::PlainText {
+            text: text.into(),
+            emoji: true,
+        });
+        self
+    }
+
+    /// Set a url to open in the browser when the button is clicked.
+    #[must_use]
+    pub fn url<S: Into<String>>(mut self, url: S) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Set the value sent along with the triggered action.
+    #[must_use]
+    pub fn value<S: Into<String>>(mut self, value: S) -> Self {
+        self.value = value.into();
+        self
+    }
+
+    /// Set the [`ButtonStyle`] of the button.
+    #[must_use]
+    pub fn style(mut self, style: ButtonStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+}
+
+/// A small image element, used as a section accessory or inside a [`Context`] block.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImageElement {
+    image_url: String,
+
+    alt_text: String,
+}
+
+impl ImageElement {
+    /// Create a new [`ImageElement`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Set the url of the image.
+    #[must_use]
+    pub fn image_url<S: Into<String>>(mut self, image_url: S) -> Self {
+        self.image_url = image_url.into();
+        self
+    }
+
+    /// Set the description of the image, displayed when the image cannot be shown.
+    #[must_use]
+    pub fn alt_text<S: Into<String>>(mut self, alt_text: S) -> Self {
+        self.alt_text = alt_text.into();
+        self
+    }
+}
+
+/// An element nested inside of a block.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    PlainText {
+        text: String,
+
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        emoji: bool,
+    },
+    Mrkdwn {
+        text: String,
+    },
+    Button(Button),
+    Image(ImageElement),
+}
+
+impl From<Text> for Element {
+    fn from(text: Text) -> Self {
+        match text {
+            Text::PlainText { text, emoji } => Element::PlainText { text, emoji },
+            Text::Mrkdwn { text } => Element::Mrkdwn { text },
+        }
+    }
+}
+
+impl From<Button> for Element {
+    fn from(button: Button) -> Self {
+        Element::Button(button)
+    }
+}
+
+impl From<ImageElement> for Element {
+    fn from(image: ImageElement) -> Self {
+        Element::Image(image)
+    }
+}
+
+/// A block of text, with optional fields and an accessory element.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Section {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    block_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<Text>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<Text>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accessory: Option<Element>,
+}
+
+impl Section {
+    /// Create a new [`Section`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Set the identifier of the block.
+    #[must_use]
+    pub fn block_id<S: Into<String>>(mut self, block_id: S) -> Self {
+        self.block_id = block_id.into();
+        self
+    }
+
+    /// Set the text of the section.
+    #[must_use]
+    pub fn text<T: Into<Text>>(mut self, text: T) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Add a field to the section. Fields are displayed in two columns.
+    #[must_use]
+    pub fn field<T: Into<Text>>(mut self, field: T) -> Self {
+        self.fields.push(field.into());
+        self
+    }
+
+    /// Set an element displayed alongside the section text, like a [`Button`] or [`ImageElement`].
+    #[must_use]
+    pub fn accessory<E: Into<Element>>(mut self, accessory: E) -> Self {
+        self.accessory = Some(accessory.into());
+        self
+    }
+}
+
+/// A block of small, secondary text and images.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Context {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    block_id: String,
+
+    elements: Vec<Element>,
+}
+
+impl Context {
+    /// Create a new [`Context`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Set the identifier of the block.
+    #[must_use]
+    pub fn block_id<S: Into<String>>(mut self, block_id: S) -> Self {
+        self.block_id = block_id.into();
+        self
+    }
+
+    /// Add a [`Text`] or [`ImageElement`] to the context.
+    #[must_use]
+    pub fn element<E: Into<Element>>(mut self, element: E) -> Self {
+        self.elements.push(element.into());
+        self
+    }
+}
+
+/// A block of interactive elements, like [`Button`]s.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Actions {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    block_id: String,
+
+    elements: Vec<Element>,
+}
+
+impl Actions {
+    /// Create a new [`Actions`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Set the identifier of the block.
+    #[must_use]
+    pub fn block_id<S: Into<String>>(mut self, block_id: S) -> Self {
+        self.block_id = block_id.into();
+        self
+    }
+
+    /// Add an interactive element to the block.
+    #[must_use]
+    pub fn element<E: Into<Element>>(mut self, element: E) -> Self {
+        self.elements.push(element.into());
+        self
+    }
+}
+
+/// A block displaying a single, large image.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Image {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    block_id: String,
+
+    #[serde(rename = "imageUrl")]
+    url: String,
+
+    alt_text: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<Text>,
+}
+
+impl Image {
+    /// Create a new [`Image`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Set the identifier of the block.
+    #[must_use]
+    pub fn block_id<S: Into<String>>(mut self, block_id: S) -> Self {
+        self.block_id = block_id.into();
+        self
+    }
+
+    /// Set the url of the image.
+    #[must_use]
+    pub fn image_url<S: Into<String>>(mut self, image_url: S) -> Self {
+        self.url = image_url.into();
+        self
+    }
+
+    /// Set the description of the image, displayed when the image cannot be shown.
+    #[must_use]
+    pub fn alt_text<S: Into<String>>(mut self, alt_text: S) -> Self {
+        self.alt_text = alt_text.into();
+        self
+    }
+
+    /// Set a title displayed above the image.
+    #[must_use]
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(Text::plain(title));
+        self
+    }
+}
+
+/// A `UIKit` layout block.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Section(Section),
+    Divider,
+    Context(Context),
+    Actions(Actions),
+    Image(Image),
+}
+
+impl From<Section> for Block {
+    fn from(section: Section) -> Self {
+        Block::Section(section)
+    }
+}
+
+impl From<Context> for Block {
+    fn from(context: Context) -> Self {
+        Block::Context(context)
+    }
+}
+
+impl From<Actions> for Block {
+    fn from(actions: Actions) -> Self {
+        Block::Actions(actions)
+    }
+}
+
+impl From<Image> for Block {
+    fn from(image: Image) -> Self {
+        Block::Image(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn to_json<B: Into<Block>>(block: B) -> Value {
+        serde_json::to_value(block.into()).unwrap()
+    }
+
+    #[test]
+    fn section() {
+        let section = Section::new()
+            .block_id("summary")
+            .text(Text::markdown("*Deploy finished*"))
+            .field(Text::markdown("*Env*\nprod"))
+            .field("42s")
+            .accessory(
+                ImageElement::new()
+                    .image_url("https://ci.example.com/logo.png")
+                    .alt_text("CI"),
+            );
+        assert_eq!(
+            to_json(section),
+            json!({
+                "type": "section",
+                "blockId": "summary",
+                "text": { "type": "mrkdwn", "text": "*Deploy finished*" },
+                "fields": [
+                    { "type": "mrkdwn", "text": "*Env*\nprod" },
+                    { "type": "plain_text", "text": "42s" }
+                ],
+                "accessory": {
+                    "type": "image",
+                    "imageUrl": "https://ci.example.com/logo.png",
+                    "altText": "CI"
+                }
+            })
+        );
+        assert_eq!(to_json(Section::new()), json!({ "type": "section" }));
+    }
+
+    #[test]
+    fn section_with_button() {
+        let section = Section::new()
+            .text("Rollback?")
+            .accessory(Button::new().action_id("rollback").text("Roll back"));
+        assert_eq!(
+            to_json(section)["accessory"],
+            json!({
+                "type": "button",
+                "actionId": "rollback",
+                "text": { "type": "plain_text", "text": "Roll back", "emoji": true }
+            })
+        );
+    }
+
+    #[test]
+    fn context() {
+        let context = Context::new()
+            .block_id("meta")
+            .element(Text::plain("took 42s"))
+            .element(
+                ImageElement::new()
+                    .image_url("https://example.com/a.png")
+                    .alt_text("avatar"),
+            );
+        assert_eq!(
+            to_json(context),
+            json!({
+                "type": "context",
+                "blockId": "meta",
+                "elements": [
+                    { "type": "plain_text", "text": "took 42s" },
+                    { "type": "image", "imageUrl": "https://example.com/a.png", "altText": "avatar" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn actions() {
+        let actions = Actions::new().block_id("links").element(
+            Button::new()
+                .action_id("open-logs")
+                .block_id("links")
+                .app_id("ci-app")
+                .text("Open logs")
+                .url("https://ci.example.com/jobs/42")
+                .value("42")
+                .style(ButtonStyle::Danger),
+        );
+        assert_eq!(
+            to_json(actions),
+            json!({
+                "type": "actions",
+                "blockId": "links",
+                "elements": [{
+                    "type": "button",
+                    "actionId": "open-logs",
+                    "blockId": "links",
+                    "appId": "ci-app",
+                    "text": { "type": "plain_text", "text": "Open logs", "emoji": true },
+                    "url": "https://ci.example.com/jobs/42",
+                    "value": "42",
+                    "style": "danger"
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn image() {
+        let image = Image::new()
+            .block_id("graph")
+            .image_url("https://grafana.example.com/render.png")
+            .alt_text("CPU graph")
+            .title("CPU");
+        assert_eq!(
+            to_json(image),
+            json!({
+                "type": "image",
+                "blockId": "graph",
+                "imageUrl": "https://grafana.example.com/render.png",
+                "altText": "CPU graph",
+                "title": { "type": "plain_text", "text": "CPU" }
+            })
+        );
+    }
+
+    #[test]
+    fn divider() {
+        assert_eq!(
+            serde_json::to_value(Block::Divider).unwrap(),
+            json!({ "type": "divider" })
+        );
+    }
+
+    #[test]
+    fn blocks_read_back() {
+        let blocks = json!([
+            { "type": "section", "blockId": "a", "text": { "type": "mrkdwn", "text": "hi" } },
+            { "type": "divider" },
+            { "type": "actions", "elements": [{ "type": "button", "actionId": "b", "style": "primary" }] },
+            { "type": "image", "imageUrl": "https://example.com/a.png", "altText": "a" }
+        ]);
+        let parsed: Vec<Block> = serde_json::from_value(blocks.clone()).unwrap();
+        assert_eq!(serde_json::to_value(parsed).unwrap(), blocks);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("invalid color `{0}`")]
+pub struct ParseColorError(String);
+
+/// The color of an [`Attachment`](crate::request::Attachment) border.
+///
+/// ### Example:
+/// ```
+/// use rocketchat::color::Color;
+/// let warning: Color = "warning".parse().unwrap();
+/// assert_eq!(warning, Color::Warning);
+///
+/// // CSS colors are normalized to hex
+/// let custom: Color = "rgb(255, 0, 128)".parse().unwrap();
+/// assert_eq!(custom.to_string(), "#ff0080");
+///
+/// // strings are sent as they are, only parsing turns levels into colors
+/// assert_eq!(Color::from("warning").to_string(), "warning");
+///
+/// assert!("not-a-color".parse::<Color>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Color {
+    /// Something completed successfully
+    Success,
+
+    /// Informational
+    Info,
+
+    /// Something may need attention
+    Warning,
+
+    /// Something failed
+    Danger,
+
+    /// No particular significance
+    Neutral,
+
+    /// Any CSS [color](https://developer.mozilla.org/en-US/docs/Web/CSS/color_value) value
+    Css(String),
+}
+
+impl Color {
+    /// The CSS value of the color, as sent to Rocket.Chat.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Color::Success => "#2de0a5",
+            Color::Info => "#1d74f5",
+            Color::Warning => "#ffd21f",
+            Color::Danger => "#f5455c",
+            Color::Neutral => "#9ea2a8",
+            Color::Css(css) => css,
+        }
+    }
+
+    fn from_level(level: &str) -> Option<Self> {
+        match level.to_ascii_lowercase().as_str() {
+            "success" | "good" => Some(Color::Success),
+            "info" => Some(Color::Info),
+            "warning" | "warn" => Some(Color::Warning),
+            "danger" | "error" => Some(Color::Danger),
+            "neutral" => Some(Color::Neutral),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    /// Parse a semantic level like `success`, `info`, `warning`, `danger` or `neutral`, or a valid CSS color, which
+    /// is normalized to hex.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(color) = Color::from_level(s) {
+            return Ok(color);
+        }
+        csscolorparser::parse(s)
+            .map(|color| Color::Css(color.to_css_hex()))
+            .map_err(|_| ParseColorError(s.to_string()))
+    }
+}
+
+/// Passes the value through to Rocket.Chat as it is, unvalidated. Use [`str::parse`] to validate CSS colors and
+/// convert semantic levels.
+impl From<&str> for Color {
+    fn from(s: &str) -> Self {
+        Color::Css(s.to_string())
+    }
+}
+
+impl From<String> for Color {
+    fn from(s: String) -> Self {
+        Color::Css(s)
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Color::from)
+    }
+}
@@ -8,6 +8,7 @@
 //! ```no_run
 //! use rocketchat::{
 //!     client::Client,
+//!     color::Color,
 //!     request::{Attachment, Message},
 //! };
 //!
@@ -19,12 +20,13 @@
 //!         Attachment::new()
 //!             .title("Hello, World")
 //!             .text("A sample message body")
-//!             .color("green")
+//!             .color(Color::Success)
 //!             .collapsed(false),
 //!     );
 //!
 //! let webhook_url = "https://my.rocket.com/hooks/db78d646/b072678678e8c74a";
 //! let response = Client::new(webhook_url)
+//!     .expect("Invalid webhook URL")
 //!     .send(&message)
 //!     .expect("Failed to send message");
 //!
@@ -41,6 +43,9 @@ compile_error!("Both client features `rustls` and `native-tls` cannot both be en
 #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
 compile_error!("You must enable either the `rustls` or `native-tls` transport features");
 
+pub mod blocks;
 pub mod client;
+pub mod color;
 pub mod request;
 pub mod response;
+pub mod webhook;
@@ -1,5 +1,149 @@
-use chrono::{DateTime, Local};
-use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, MapAccess, Visitor},
+    ser::SerializeMap,
+};
+
+use crate::{blocks::Block, color::Color};
+
+/// Where a [`Message`] is to be sent.
+///
+/// ### Example:
+/// ```
+/// use rocketchat::request::Destination;
+/// let destination: Destination = "@myusername".parse().unwrap();
+/// assert_eq!(destination, Destination::User("myusername".into()));
+/// assert_eq!(destination.to_string(), "@myusername");
+///
+/// // channels are sent as they are given
+/// let destination: Destination = "general".parse().unwrap();
+/// assert_eq!(destination, Destination::Channel("general".into()));
+/// assert_eq!(destination.to_string(), "general");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// A channel, as given, like `#general`, or `general` which Rocket.Chat also treats as a channel
+    Channel(String),
+
+    /// A direct message to a user, by username without the leading `@`
+    User(String),
+
+    /// A room, by id
+    RoomId(String),
+}
+
+impl Destination {
+    // The destination of a payload with these fields, where empty fields are unset
+    fn from_fields(
+        room_id: Option<String>,
+        channel: Option<String>,
+    ) -> Result<Option<Self>, &'static str> {
+        let room_id = room_id.filter(|room_id| !room_id.is_empty());
+        let channel = channel.filter(|channel| !channel.is_empty());
+        match (room_id, channel) {
+            (Some(_), Some(_)) => Err("`channel` and `roomId` can't both be set"),
+            (Some(room_id), None) => Ok(Some(Destination::RoomId(room_id))),
+            (None, Some(channel)) => Ok(Some(channel.parse().unwrap_or_else(|e| match e {}))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl FromStr for Destination {
+    type Err = Infallible;
+
+    /// Parse a destination with a prefix in front of it. `#` refers to a channel, `@` refers to a username.
+    /// Destinations without a prefix are treated as channels.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(if let Some(user) = s.strip_prefix('@') {
+            Destination::User(user.to_string())
+        } else {
+            Destination::Channel(s.to_string())
+        })
+    }
+}
+
+impl Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Destination::Channel(channel) => f.write_str(channel),
+            Destination::User(user) => write!(f, "@{user}"),
+            Destination::RoomId(room_id) => f.write_str(room_id),
+        }
+    }
+}
+
+impl Serialize for Destination {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+            Destination::RoomId(room_id) => map.serialize_entry("roomId", room_id)?,
+            _ => map.serialize_entry("channel", &self.to_string())?,
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Destination {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Fields {
+            room_id: Option<String>,
+            channel: Option<String>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        Destination::from_fields(fields.room_id, fields.channel)
+            .map_err(de::Error::custom)?
+            .ok_or_else(|| de::Error::missing_field("channel"))
+    }
+}
+
+/// The icon displayed next to the sender of a [`Message`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Avatar {
+    /// An emoji, like `:computer:`
+    Emoji(String),
+
+    /// An image url
+    Url(String),
+}
+
+impl Serialize for Avatar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+            Avatar::Emoji(emoji) => map.serialize_entry("emoji", emoji)?,
+            Avatar::Url(url) => map.serialize_entry("avatar", url)?,
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Avatar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            emoji: Option<String>,
+            avatar: Option<String>,
+        }
+
+        // Rocket.Chat preferentially uses avatar instead of emoji
+        match Fields::deserialize(deserializer)? {
+            Fields {
+                avatar: Some(url), ..
+            } => Ok(Avatar::Url(url)),
+            Fields {
+                emoji: Some(emoji), ..
+            } => Ok(Avatar::Emoji(emoji)),
+            _ => Err(serde::de::Error::missing_field("emoji")),
+        }
+    }
+}
 
 /// A Rocket.Chat Message payload.
 ///
@@ -8,22 +152,22 @@ use serde::{Deserialize, Serialize};
 /// use rocketchat::request::Message;
 /// let message = Message::new()
 ///     .room_id("ByehQjC44FwMeiLbX")
-///     // either room_id or channel can be used
+///     // either room_id or channel can be used, the last one set wins
 ///     // .channel("@myusername")
 ///     .text("Hello, world!")
 ///     .alias("My.Bot")
-///     // Rocket.Chat preferentially uses avatar instead of emoji
+///     // either an emoji or an avatar url can be used, the last one set wins
 ///     // .avatar("https://foo.bar/computer.png)
 ///     .emoji(":computer:");
 /// ```
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Message {
-    #[serde(skip_serializing_if = "String::is_empty")]
-    room_id: String,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    destination: Option<Destination>,
 
     #[serde(skip_serializing_if = "String::is_empty")]
-    channel: String,
+    tmid: String,
 
     #[serde(skip_serializing_if = "String::is_empty")]
     text: String,
@@ -31,13 +175,52 @@ pub struct Message {
     #[serde(skip_serializing_if = "String::is_empty")]
     alias: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
-    emoji: String,
-
-    #[serde(skip_serializing_if = "String::is_empty")]
-    avatar: String,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    icon: Option<Avatar>,
 
     attachments: Vec<Attachment>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    blocks: Vec<Block>,
+}
+
+// Flattened fields are buffered before they are deserialized, which loses values like TOML datetimes, so messages
+// are read field by field instead
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Default, Deserialize)]
+        #[serde(rename_all = "camelCase", default)]
+        struct Fields {
+            room_id: Option<String>,
+            channel: Option<String>,
+            tmid: String,
+            text: String,
+            alias: String,
+            emoji: Option<String>,
+            avatar: Option<String>,
+            attachments: Vec<Attachment>,
+            blocks: Vec<Block>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let destination =
+            Destination::from_fields(fields.room_id, fields.channel).map_err(de::Error::custom)?;
+        // Rocket.Chat preferentially uses avatar instead of emoji
+        let icon = match (fields.avatar, fields.emoji) {
+            (Some(url), _) => Some(Avatar::Url(url)),
+            (None, Some(emoji)) => Some(Avatar::Emoji(emoji)),
+            (None, None) => None,
+        };
+        Ok(Message {
+            destination,
+            tmid: fields.tmid,
+            text: fields.text,
+            alias: fields.alias,
+            icon,
+            attachments: fields.attachments,
+            blocks: fields.blocks,
+        })
+    }
 }
 
 impl Message {
@@ -49,23 +232,42 @@ impl Message {
         }
     }
 
-    /// Set the room id of where the message is to be sent. Resets
+    /// Set the [`Destination`] where the message is to be sent.
+    #[must_use]
+    pub fn destination(mut self, destination: Destination) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+
+    /// Set the room id of where the message is to be sent. Replaces any previously set [`Destination`], and an
+    /// empty id unsets it.
     #[must_use]
     pub fn room_id<S: Into<String>>(mut self, room_id: S) -> Self {
-        self.room_id = room_id.into();
-        if !self.channel.is_empty() && !self.room_id.is_empty() {
-            self.channel = String::default();
-        }
+        self.destination = Destination::from_fields(Some(room_id.into()), None).unwrap_or_default();
         self
     }
 
-    /// Set the channel where the message is to be sent. Set the channel where the message is to be sent, with the prefix in front of it. `#` refers to channel, `@` refers to a username
+    /// Set the channel where the message is to be sent, with the prefix in front of it. `#` refers to channel, `@` refers to a username.
+    /// Replaces any previously set [`Destination`], and an empty channel unsets it.
     #[must_use]
     pub fn channel<S: Into<String>>(mut self, channel: S) -> Self {
-        self.channel = channel.into();
-        if !self.room_id.is_empty() && !self.channel.is_empty() {
-            self.room_id = String::default();
-        }
+        self.destination = Destination::from_fields(None, Some(channel.into())).unwrap_or_default();
+        self
+    }
+
+    /// Set the user to whom the message is to be sent as a direct message. Replaces any previously set [`Destination`],
+    /// and an empty username unsets it.
+    #[must_use]
+    pub fn user<S: Into<String>>(mut self, username: S) -> Self {
+        let username = username.into();
+        self.destination = (!username.is_empty()).then_some(Destination::User(username));
+        self
+    }
+
+    /// Set the id of a message to reply to in a thread. See [`response::Message::thread_id`](crate::response::Message::thread_id).
+    #[must_use]
+    pub fn tmid<S: Into<String>>(mut self, tmid: S) -> Self {
+        self.tmid = tmid.into();
         self
     }
 
@@ -83,17 +285,24 @@ impl Message {
         self
     }
 
-    /// Set the avatar on the message to be an emoji.
+    /// Set the [`Avatar`] displayed next to the message sender.
+    #[must_use]
+    pub fn icon(mut self, icon: Avatar) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Set the avatar on the message to be an emoji. Replaces any previously set [`Avatar`].
     #[must_use]
     pub fn emoji<S: Into<String>>(mut self, emoji: S) -> Self {
-        self.emoji = emoji.into();
+        self.icon = Some(Avatar::Emoji(emoji.into()));
         self
     }
 
-    /// Set the avatar of the message to the provided image url.
+    /// Set the avatar of the message to the provided image url. Replaces any previously set [`Avatar`].
     #[must_use]
     pub fn avatar<S: Into<String>>(mut self, avatar: S) -> Self {
-        self.avatar = avatar.into();
+        self.icon = Some(Avatar::Url(avatar.into()));
         self
     }
 
@@ -110,6 +319,44 @@ impl Message {
         self.attachments = attachments;
         self
     }
+
+    /// Mutable access to the [`Attachment`]s of the message, like for updating a message read from a file.
+    pub fn attachments_mut(&mut self) -> &mut Vec<Attachment> {
+        &mut self.attachments
+    }
+
+    /// The [`Destination`] where the message is to be sent, if one was set.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rocketchat::request::{Destination, Message};
+    /// let message = Message::new().channel("@myusername");
+    /// assert_eq!(message.get_destination(), Some(&Destination::User("myusername".into())));
+    /// ```
+    #[must_use]
+    pub fn get_destination(&self) -> Option<&Destination> {
+        self.destination.as_ref()
+    }
+
+    /// The alias the message is sent as, or an empty string if none was set.
+    #[must_use]
+    pub fn get_alias(&self) -> &str {
+        &self.alias
+    }
+
+    /// Add a `UIKit` [`Block`] to the message
+    #[must_use]
+    pub fn block<B: Into<Block>>(mut self, block: B) -> Self {
+        self.blocks.push(block.into());
+        self
+    }
+
+    /// Add multiple `UIKit` [`Block`] to the message
+    #[must_use]
+    pub fn blocks(mut self, blocks: Vec<Block>) -> Self {
+        self.blocks = blocks;
+        self
+    }
 }
 
 /// Attachment fields that annotate an [`Attachment`]. Allows for "tables" or "columns" to be displayed on messages.
@@ -121,7 +368,8 @@ impl Message {
 ///     .short(true)
 ///     .title("");
 /// ```
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct AttachmentField {
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     short: bool,
@@ -164,25 +412,71 @@ impl AttachmentField {
     }
 }
 
+// Key of the map TOML datetimes are read as, holding the datetime as a string
+const TOML_DATETIME: &str = "$__toml_private_datetime";
+
+// Read an attachment time from an RFC 3339 string, or a TOML datetime
+fn deserialize_ts<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    struct Ts;
+
+    impl<'de> Visitor<'de> for Ts {
+        type Value = Option<DateTime<Utc>>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("an RFC 3339 formatted date and time")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+            s.parse().map(Some).map_err(E::custom)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            match map.next_entry::<String, String>()? {
+                Some((key, value)) if key == TOML_DATETIME => self.visit_str(&value),
+                _ => Err(de::Error::invalid_type(de::Unexpected::Map, &self)),
+            }
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+    }
+
+    deserializer.deserialize_any(Ts)
+}
+
 /// Message attachment, containing additional data to be rendered in the message.
 ///
 /// ### Example:
 /// ```
+/// use chrono::{TimeZone, Utc};
 /// use rocketchat::request::Attachment;
 /// let attachment = Attachment::new()
 ///     .title("Example attachment")
-///     .text("This is an example attachment");
-///     
+///     .text("This is an example attachment")
+///     // defaults to the time the attachment was created
+///     .ts(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap());
 /// ```
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Attachment {
-    #[serde(skip_serializing_if = "String::is_empty")]
-    color: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<Color>,
 
     #[serde(skip_serializing_if = "String::is_empty")]
     text: String,
 
-    ts: DateTime<Local>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_ts"
+    )]
+    ts: Option<DateTime<Utc>>,
 
     #[serde(skip_serializing_if = "String::is_empty")]
     thumb_url: String,
@@ -229,27 +523,38 @@ impl Attachment {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            ts: Local::now(),
+            ts: Some(Utc::now()),
             ..Default::default()
         }
     }
 
-    /// Set the attachment left border color. Can be any [background-color](https://developer.mozilla.org/en-US/docs/Web/CSS/background-color) value.
+    /// Set the attachment left border [`Color`]. Can be a semantic level like [`Color::Success`], or any
+    /// [background-color](https://developer.mozilla.org/en-US/docs/Web/CSS/background-color) value.
     #[must_use]
-    pub fn color<S: Into<String>>(mut self, color: S) -> Self {
-        self.color = color.into();
-        self.update_ts()
+    pub fn color<C: Into<Color>>(mut self, color: C) -> Self {
+        self.color = Some(color.into());
+        self
     }
 
     /// Set the text to display for the attachment, it is different than the message's text.
     #[must_use]
     pub fn text<S: Into<String>>(mut self, text: S) -> Self {
         self.text = text.into();
-        self.update_ts()
+        self
     }
 
-    fn update_ts(mut self) -> Self {
-        self.ts = Local::now();
+    /// Set the time displayed on the attachment, like when the event it describes occurred. Defaults to the time
+    /// the attachment was created.
+    #[must_use]
+    pub fn ts<T: Into<DateTime<Utc>>>(mut self, ts: T) -> Self {
+        self.ts = Some(ts.into());
+        self
+    }
+
+    /// Omit the time from the attachment.
+    #[must_use]
+    pub fn without_ts(mut self) -> Self {
+        self.ts = None;
         self
     }
 
@@ -299,7 +604,7 @@ impl Attachment {
     #[must_use]
     pub fn title<S: Into<String>>(mut self, title: S) -> Self {
         self.title = title.into();
-        self.update_ts()
+        self
     }
 
     /// Set the this attachment title to a clickable link.
@@ -351,3 +656,89 @@ impl Attachment {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn message_from_toml_with_datetime() {
+        let message: Message = toml::from_str(
+            r##"
+            channel = "#general"
+            emoji = ":tada:"
+
+            [[attachments]]
+            text = "Deployed"
+            ts = 2024-01-01T00:00:00Z
+            "##,
+        )
+        .unwrap();
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["channel"], "#general");
+        assert_eq!(json["emoji"], ":tada:");
+        assert_eq!(json["attachments"][0]["text"], "Deployed");
+        assert_eq!(
+            message.attachments[0].ts,
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn channel_sent_as_given() {
+        let json = |message: &Message| serde_json::to_value(message).unwrap();
+        assert_eq!(
+            json(&Message::new().channel("general"))["channel"],
+            "general"
+        );
+        assert_eq!(
+            json(&Message::new().channel("#general"))["channel"],
+            "#general"
+        );
+        assert_eq!(json(&Message::new().channel("@eric"))["channel"], "@eric");
+        assert_eq!(json(&Message::new().user("eric"))["channel"], "@eric");
+        assert_eq!(
+            json(&Message::new().room_id("GENERAL"))["roomId"],
+            "GENERAL"
+        );
+        assert!(json(&Message::new().channel("")).get("channel").is_none());
+        assert!(json(&Message::new().room_id("")).get("roomId").is_none());
+    }
+
+    #[test]
+    fn message_with_channel_and_room_id() {
+        let both =
+            serde_json::from_str::<Message>(r##"{"channel": "#general", "roomId": "GENERAL"}"##);
+        assert!(both.unwrap_err().to_string().contains("can't both be set"));
+
+        // Empty fields are unset
+        let message: Message =
+            serde_json::from_str(r##"{"channel": "#general", "roomId": ""}"##).unwrap();
+        assert_eq!(
+            message.get_destination(),
+            Some(&Destination::Channel("#general".into()))
+        );
+    }
+
+    #[test]
+    fn message_from_json() {
+        let message: Message = serde_json::from_str(
+            r#"{"roomId": "GENERAL", "avatar": "https://example.com/bot.png", "emoji": ":tada:", "text": "hi", "attachments": [{"ts": "2024-01-01T00:00:00Z"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            message.get_destination(),
+            Some(&Destination::RoomId("GENERAL".into()))
+        );
+        assert_eq!(
+            message.icon,
+            Some(Avatar::Url("https://example.com/bot.png".into()))
+        );
+        assert_eq!(message.text, "hi");
+        assert_eq!(
+            message.attachments[0].ts,
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+    }
+}
@@ -1,15 +1,53 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// Unrecognized fields, preserved as they were received from the server
+pub type Extra = BTreeMap<String, Value>;
+
+// Read a time as sent by the server, which may be an RFC 3339 string, `{"$date": milliseconds}`, or seconds or
+// milliseconds since the Unix epoch. Times that can't be read are left at the epoch, rather than failing the response
+fn lenient_ts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let ts = match Value::deserialize(deserializer)? {
+        Value::String(s) => s.parse().ok(),
+        // Numbers too large to be seconds until the year 5000 are milliseconds, like `Date.now()`
+        Value::Number(n) => n.as_i64().and_then(|n| {
+            if n.abs() < 100_000_000_000 {
+                DateTime::from_timestamp(n, 0)
+            } else {
+                DateTime::from_timestamp_millis(n)
+            }
+        }),
+        Value::Object(object) => object
+            .get("$date")
+            .and_then(Value::as_i64)
+            .and_then(DateTime::from_timestamp_millis),
+        _ => None,
+    };
+    Ok(ts.unwrap_or_default())
+}
 
-use chrono::{DateTime, Local};
-use serde::Deserialize;
+// Read a string, accepting numbers and booleans as their JSON text, and null as empty
+fn lenient_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        value => value.to_string(),
+    })
+}
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct User {
-    #[serde(alias = "_id", default)]
+    #[serde(rename = "_id", alias = "id", default)]
     id: String,
 
     #[serde(default)]
     username: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String,
 }
 
 impl User {
@@ -22,17 +60,210 @@ impl User {
     pub fn username(&self) -> &String {
         &self.username
     }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+}
+
+/// A channel mentioned in a [`Message`]
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Channel {
+    #[serde(rename = "_id", alias = "id", default)]
+    id: String,
+
+    #[serde(default)]
+    name: String,
+}
+
+impl Channel {
+    #[must_use]
+    pub fn id(&self) -> &str {
+        self.id.as_ref()
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+}
+
+/// The users who reacted to a [`Message`] with a given emoji
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Reaction {
+    #[serde(default)]
+    usernames: Vec<String>,
+}
+
+impl Reaction {
+    #[must_use]
+    pub fn usernames(&self) -> &[String] {
+        &self.usernames
+    }
+}
+
+/// A field of an [`Attachment`] echoed by the server
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct AttachmentField {
+    #[serde(default, deserialize_with = "lenient_string")]
+    title: String,
+
+    #[serde(default, deserialize_with = "lenient_string")]
+    value: String,
+
+    #[serde(default)]
+    short: bool,
+}
+
+impl AttachmentField {
+    #[must_use]
+    pub fn title(&self) -> &str {
+        self.title.as_ref()
+    }
+
+    /// The field's value, where values sent as numbers or booleans are given as their JSON text
+    #[must_use]
+    pub fn value(&self) -> &str {
+        self.value.as_ref()
+    }
+
+    #[must_use]
+    pub fn short(&self) -> bool {
+        self.short
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// An attachment of a [`Message`], as echoed by the server.
+///
+/// Servers and integration scripts may send attachments not built with [`crate::request::Attachment`], so fields are
+/// read leniently, and any not modeled are kept in [`Attachment::extra`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Attachment {
+    #[serde(default, deserialize_with = "lenient_string")]
+    title: String,
+
+    #[serde(default, deserialize_with = "lenient_string")]
+    title_link: String,
+
+    #[serde(default, deserialize_with = "lenient_string")]
+    text: String,
+
+    #[serde(default, deserialize_with = "lenient_string")]
+    color: String,
+
+    #[serde(default, deserialize_with = "lenient_ts")]
+    ts: DateTime<Utc>,
+
+    #[serde(default, deserialize_with = "lenient_string")]
+    image_url: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<AttachmentField>,
+
+    #[serde(flatten)]
+    extra: Extra,
+}
+
+impl Attachment {
+    #[must_use]
+    pub fn title(&self) -> &str {
+        self.title.as_ref()
+    }
+
+    #[must_use]
+    pub fn title_link(&self) -> &str {
+        self.title_link.as_ref()
+    }
+
+    #[must_use]
+    pub fn text(&self) -> &str {
+        self.text.as_ref()
+    }
+
+    /// The color as sent, like `#ff0000` or `danger`
+    #[must_use]
+    pub fn color(&self) -> &str {
+        self.color.as_ref()
+    }
+
+    /// The attachment's time, or the Unix epoch if it has none the server sent in a known format
+    #[must_use]
+    pub fn ts(&self) -> DateTime<Utc> {
+        self.ts
+    }
+
+    #[must_use]
+    pub fn image_url(&self) -> &str {
+        self.image_url.as_ref()
+    }
+
+    #[must_use]
+    pub fn fields(&self) -> &[AttachmentField] {
+        &self.fields
+    }
+
+    /// Any fields of the attachment not otherwise modeled
+    #[must_use]
+    pub fn extra(&self) -> &Extra {
+        &self.extra
+    }
+}
+
+/// A url contained in a [`Message`], along with any preview metadata fetched by the server
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UrlPreview {
+    #[serde(default)]
+    url: String,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    meta: Extra,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: Extra,
+}
+
+impl UrlPreview {
+    #[must_use]
+    pub fn url(&self) -> &str {
+        self.url.as_ref()
+    }
+
+    #[must_use]
+    pub fn meta(&self) -> &Extra {
+        &self.meta
+    }
+
+    #[must_use]
+    pub fn headers(&self) -> &Extra {
+        &self.headers
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Message {
+    #[serde(rename = "_id", alias = "id", default)]
+    id: String,
+
+    #[serde(default)]
+    rid: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tmid: Option<String>,
+
     #[serde(default)]
     alias: String,
 
     #[serde(default)]
     msg: String,
 
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    emoji: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    avatar: String,
+
     #[serde(default)]
     parse_urls: bool,
 
@@ -42,20 +273,60 @@ pub struct Message {
     #[serde(default)]
     u: User,
 
-    #[serde(default)]
-    ts: DateTime<Local>,
+    #[serde(default, deserialize_with = "lenient_ts")]
+    ts: DateTime<Utc>,
 
-    #[serde(default)]
-    rid: String,
+    #[serde(
+        rename = "_updatedAt",
+        alias = "updatedAt",
+        default,
+        deserialize_with = "lenient_ts"
+    )]
+    updated_at: DateTime<Utc>,
 
-    #[serde(default)]
-    updated_at: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Attachment>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mentions: Vec<User>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    channels: Vec<Channel>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    reactions: BTreeMap<String, Reaction>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    urls: Vec<UrlPreview>,
 
-    #[serde(alias = "_id", default)]
-    id: bool,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
 impl Message {
+    #[must_use]
+    pub fn id(&self) -> &str {
+        self.id.as_ref()
+    }
+
+    #[must_use]
+    pub fn rid(&self) -> &str {
+        self.rid.as_ref()
+    }
+
+    /// The id of the thread the message was posted in, if any
+    #[must_use]
+    pub fn tmid(&self) -> Option<&str> {
+        self.tmid.as_deref()
+    }
+
+    /// The id to reply to in order to continue the message's thread, which is either the thread the message was
+    /// posted in, or the message itself
+    #[must_use]
+    pub fn thread_id(&self) -> &str {
+        self.tmid.as_deref().unwrap_or(&self.id)
+    }
+
     #[must_use]
     pub fn alias(&self) -> &str {
         self.alias.as_ref()
@@ -66,6 +337,16 @@ impl Message {
         self.msg.as_ref()
     }
 
+    #[must_use]
+    pub fn emoji(&self) -> &str {
+        self.emoji.as_ref()
+    }
+
+    #[must_use]
+    pub fn avatar(&self) -> &str {
+        self.avatar.as_ref()
+    }
+
     #[must_use]
     pub fn parse_urls(&self) -> bool {
         self.parse_urls
@@ -82,23 +363,45 @@ impl Message {
     }
 
     #[must_use]
-    pub fn ts(&self) -> DateTime<Local> {
+    pub fn ts(&self) -> DateTime<Utc> {
         self.ts
     }
 
     #[must_use]
-    pub fn rid(&self) -> &str {
-        self.rid.as_ref()
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
     }
 
     #[must_use]
-    pub fn updated_at(&self) -> DateTime<Local> {
-        self.updated_at
+    pub fn attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    #[must_use]
+    pub fn mentions(&self) -> &[User] {
+        &self.mentions
+    }
+
+    #[must_use]
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    /// Reactions to the message, keyed by emoji
+    #[must_use]
+    pub fn reactions(&self) -> &BTreeMap<String, Reaction> {
+        &self.reactions
+    }
+
+    #[must_use]
+    pub fn urls(&self) -> &[UrlPreview] {
+        &self.urls
     }
 
+    /// Any fields of the message not otherwise modeled
     #[must_use]
-    pub fn id(&self) -> bool {
-        self.id
+    pub fn extra(&self) -> &Extra {
+        &self.extra
     }
 }
 
@@ -114,18 +417,29 @@ impl Message {
 ///     .text("Hello, world!");
 ///
 /// let response = Client::new("https://my.rocket.com/hooks/db78d646/b072678678e8c74a")
+///     .unwrap()
 ///     .send(&message)
 ///     .unwrap();
 ///
 /// if response.success() {
 ///     // Action
 /// }
+///
+/// // Reply in a thread under the message that was just sent
+/// let reply = Message::new()
+///     .room_id(response.message().rid())
+///     .tmid(response.message().thread_id())
+///     .text("More details...");
+///
+/// if let Some(link) = response.permalink("https://my.rocket.com") {
+///     println!("Posted at {link}");
+/// }
 /// ```
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Response {
-    #[serde(default)]
-    ts: DateTime<Local>,
+    #[serde(default, deserialize_with = "lenient_ts")]
+    ts: DateTime<Utc>,
 
     #[serde(default)]
     channel: String,
@@ -136,17 +450,20 @@ pub struct Response {
     #[serde(default)]
     success: bool,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     error: String,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     error_type: String,
+
+    #[serde(flatten)]
+    extra: Extra,
 }
 
 // TODO: Make this an enum type like hyper
 impl Response {
     #[must_use]
-    pub fn ts(&self) -> DateTime<Local> {
+    pub fn ts(&self) -> DateTime<Utc> {
         self.ts
     }
 
@@ -174,6 +491,88 @@ impl Response {
     pub fn error_type(&self) -> &String {
         &self.error_type
     }
+
+    /// Any fields of the response not otherwise modeled, like those returned by incoming webhook scripts
+    #[must_use]
+    pub fn extra(&self) -> &Extra {
+        &self.extra
+    }
+
+    /// A link to the sent message on the Rocket.Chat server at `server_url`, like `https://my.rocket.com`.
+    ///
+    /// Messages sent to a user link to the direct message room, all others link to the channel by name. Returns
+    /// `None` if the response does not identify the sent message.
+    ///
+    /// ### Example
+    /// ```
+    /// use rocketchat::response::Response;
+    ///
+    /// let response: Response = serde_json::from_str(
+    ///     r#"{ "channel": "general", "success": true, "message": { "_id": "7aDSXtjMA3KPLxLjt", "rid": "GENERAL" } }"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(
+    ///     response.permalink("https://my.rocket.com/").as_deref(),
+    ///     Some("https://my.rocket.com/channel/general?msg=7aDSXtjMA3KPLxLjt")
+    /// );
+    /// ```
+    #[must_use]
+    pub fn permalink(&self, server_url: &str) -> Option<String> {
+        let id = self.message.id();
+        if id.is_empty() {
+            return None;
+        }
+
+        let server_url = server_url.trim_end_matches('/');
+        if self.channel.starts_with('@') || self.channel.is_empty() {
+            let rid = self.message.rid();
+            (!rid.is_empty()).then(|| format!("{server_url}/direct/{rid}?msg={id}"))
+        } else {
+            let channel = self.channel.trim_start_matches('#');
+            Some(format!("{server_url}/channel/{channel}?msg={id}"))
+        }
+    }
+}
+
+/// Information about a Rocket.Chat server, from its `/api/info` endpoint
+///
+/// ### Example
+/// ```
+/// use rocketchat::response::ServerInfo;
+///
+/// let info: ServerInfo = serde_json::from_str(r#"{ "version": "6.5.0", "success": true }"#).unwrap();
+/// assert_eq!(info.version(), Some("6.5.0"));
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ServerInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+
+    #[serde(default)]
+    success: bool,
+
+    #[serde(flatten)]
+    extra: Extra,
+}
+
+impl ServerInfo {
+    /// The Rocket.Chat version, if the server shares it without logging in
+    #[must_use]
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    #[must_use]
+    pub fn success(&self) -> bool {
+        self.success
+    }
+
+    /// Any fields of the response not otherwise modeled
+    #[must_use]
+    pub fn extra(&self) -> &Extra {
+        &self.extra
+    }
 }
 
 impl Display for Response {
@@ -191,3 +590,103 @@ impl Display for Response {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The response to `chat.postMessage`, echoing a message with attachments from an integration
+    const POST_MESSAGE: &str = r##"{
+        "ts": 1700000000123,
+        "channel": "#alerts",
+        "message": {
+            "rid": "GENERAL",
+            "msg": "",
+            "alias": "Prometheus",
+            "emoji": ":fire:",
+            "parseUrls": true,
+            "groupable": false,
+            "attachments": [
+                {
+                    "title": "HighCPU",
+                    "title_link": "http://prometheus/alerts",
+                    "text": "CPU above 90%",
+                    "color": "danger",
+                    "ts": 1700000000,
+                    "fields": [
+                        { "title": "Instance", "value": "web-1", "short": true },
+                        { "title": "Value", "value": 5, "short": true },
+                        { "title": "Silenced", "value": false }
+                    ],
+                    "actions": [{ "type": "button", "text": "Silence" }]
+                }
+            ],
+            "ts": { "$date": 1700000000123 },
+            "u": { "_id": "rocket.cat", "username": "rocket.cat", "name": "Rocket.Cat" },
+            "_id": "7aDSXtjMA3KPLxLjt",
+            "_updatedAt": "2023-11-14T22:13:20.123Z",
+            "mentions": [],
+            "channels": [],
+            "md": [{ "type": "PARAGRAPH", "value": [] }]
+        },
+        "success": true
+    }"##;
+
+    #[test]
+    fn post_message_response() {
+        let response: Response = serde_json::from_str(POST_MESSAGE).unwrap();
+        assert!(response.success());
+        assert_eq!(response.ts().timestamp_millis(), 1_700_000_000_123);
+
+        let message = response.message();
+        assert_eq!(message.id(), "7aDSXtjMA3KPLxLjt");
+        assert_eq!(message.alias(), "Prometheus");
+        assert_eq!(message.ts().timestamp_millis(), 1_700_000_000_123);
+        assert_eq!(message.updated_at().timestamp_millis(), 1_700_000_000_123);
+        assert!(message.extra().contains_key("md"));
+
+        let [attachment] = message.attachments() else {
+            panic!("expected one attachment");
+        };
+        assert_eq!(attachment.title(), "HighCPU");
+        assert_eq!(attachment.title_link(), "http://prometheus/alerts");
+        assert_eq!(attachment.text(), "CPU above 90%");
+        assert_eq!(attachment.color(), "danger");
+        assert_eq!(attachment.ts().timestamp(), 1_700_000_000);
+        assert!(attachment.extra().contains_key("actions"));
+        let fields: Vec<_> = attachment
+            .fields()
+            .iter()
+            .map(|field| (field.title(), field.value(), field.short()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("Instance", "web-1", true),
+                ("Value", "5", true),
+                ("Silenced", "false", false)
+            ]
+        );
+    }
+
+    #[test]
+    fn unreadable_times_are_ignored() {
+        let response: Response = serde_json::from_str(
+            r#"{ "success": true, "ts": null, "message": { "_id": "a", "ts": "yesterday", "_updatedAt": [] } }"#,
+        )
+        .unwrap();
+        assert_eq!(response.ts().timestamp(), 0);
+        assert_eq!(response.message().ts().timestamp(), 0);
+        assert_eq!(response.message().updated_at().timestamp(), 0);
+    }
+
+    #[test]
+    fn times_in_rfc_3339() {
+        let message: Message = serde_json::from_str(
+            r#"{ "ts": "2023-11-14T22:13:20.123Z", "attachments": [{ "ts": "2023-11-14T22:13:20Z" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(message.ts().timestamp_millis(), 1_700_000_000_123);
+        assert_eq!(message.attachments()[0].ts().timestamp(), 1_700_000_000);
+    }
+}